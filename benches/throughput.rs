@@ -0,0 +1,122 @@
+/*
+  Throughput benchmarks for InputReader and OutputWriter, run with `cargo bench`.
+  Save a baseline with `cargo bench -- --save-baseline main` and compare a change
+  against it with `cargo bench -- --baseline main`.
+*/
+
+#[macro_use]
+extern crate criterion;
+extern crate easy_io;
+
+use criterion::{black_box, Criterion, Throughput};
+use easy_io::{InputReader, OutputWriter};
+use std::io::{self, Write};
+
+const N: usize = 1_000_000;
+
+fn numbers() -> Vec<u64> {
+	(0..N as u64).map(|i| i * 7919 % 1_000_000_007).collect()
+}
+
+fn writer(c: &mut Criterion) {
+	let nums = numbers();
+	let mut group = c.benchmark_group("writer");
+	group.throughput(Throughput::Elements(N as u64));
+
+	group.bench_function("println_u64", |b| {
+		b.iter(|| {
+			let mut out = OutputWriter::from_writer(io::sink());
+			for &x in &nums {
+				out.println(x);
+			}
+			out.flush().unwrap();
+		})
+	});
+
+	group.bench_function("prints_slice", |b| {
+		b.iter(|| {
+			let mut out = OutputWriter::from_writer(io::sink());
+			for x in black_box(&nums[..]) {
+				out.prints(x);
+			}
+			out.s2nl();
+			out.flush().unwrap();
+		})
+	});
+
+	group.bench_function("print_u64", |b| {
+		b.iter(|| {
+			let mut out = OutputWriter::from_writer(io::sink());
+			for &x in &nums {
+				out.print(x);
+			}
+			out.flush().unwrap();
+		})
+	});
+	group.finish();
+}
+
+fn reader(c: &mut Criterion) {
+	let mut numbers_input = Vec::new();
+	for (i, x) in numbers().into_iter().enumerate() {
+		write!(
+			numbers_input,
+			"{}{}",
+			x,
+			if i % 10 == 9 { '\n' } else { ' ' }
+		)
+		.unwrap();
+	}
+	let mut words_input = Vec::new();
+	for i in 0..N {
+		let word = ["alpha", "be", "gammadeltaepsilon", "x"][i % 4];
+		write!(words_input, "{} ", word).unwrap();
+	}
+
+	let mut group = c.benchmark_group("reader");
+	group.throughput(Throughput::Elements(N as u64));
+
+	group.bench_function("next_u64", |b| {
+		b.iter(|| {
+			let mut input = InputReader::from_reader(&numbers_input[..]);
+			let mut sum = 0u64;
+			for _ in 0..N {
+				sum = sum.wrapping_add(input.next_u64());
+			}
+			sum
+		})
+	});
+
+	group.bench_function("for_each_u64", |b| {
+		b.iter(|| {
+			let mut input = InputReader::from_reader(&numbers_input[..]);
+			let mut sum = 0u64;
+			input.for_each_u64(|x| sum = sum.wrapping_add(x));
+			sum
+		})
+	});
+
+	group.bench_function("next_word", |b| {
+		b.iter(|| {
+			let mut input = InputReader::from_reader(&words_input[..]);
+			let mut len = 0;
+			for _ in 0..N {
+				len += input.next_word().len();
+			}
+			len
+		})
+	});
+
+	let giant_word = vec![b'x'; 10 << 20];
+	group.throughput(Throughput::Bytes(giant_word.len() as u64));
+	group.bench_function("giant_word", |b| {
+		b.iter(|| {
+			let mut input = InputReader::from_reader(&giant_word[..]);
+			input.next_word().len()
+		})
+	});
+	group.finish();
+}
+
+criterion_group!(benches, writer, reader);
+criterion_main!(benches);
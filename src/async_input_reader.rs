@@ -0,0 +1,173 @@
+/*
+  An async line reader for interactive judges, e.g. over TCP
+
+  Repository: https://github.com/AxlLind/EasyIO.rs
+  License: MIT
+*/
+
+use futures_io::AsyncRead;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+pub struct AsyncInputReader<R: AsyncRead + Unpin> {
+	reader: R,
+	buf: Vec<u8>,
+	bytes_read: usize,
+	current_index: usize,
+	str_buf: String,
+	// whether str_buf holds a line that was already returned, rather than the start of one
+	line_done: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncInputReader<R> {
+	pub fn from_reader(reader: R) -> Self {
+		Self {
+			reader,
+			buf: vec![0; 1 << 16],
+			bytes_read: 0,
+			current_index: 0,
+			str_buf: String::with_capacity(1 << 8),
+			line_done: false,
+		}
+	}
+
+	/// resolves to the next line, without the newline, once all of it has arrived.
+	/// Cancel safe: if the future is dropped early, the next call continues the same line
+	pub fn next_line(&mut self) -> NextLine<'_, R> {
+		if self.line_done {
+			self.str_buf.clear();
+			self.line_done = false;
+		}
+		NextLine { reader: Some(self) }
+	}
+}
+
+pub struct NextLine<'a, R: AsyncRead + Unpin + 'a> {
+	reader: Option<&'a mut AsyncInputReader<R>>,
+}
+
+impl<'a, R: AsyncRead + Unpin> Future for NextLine<'a, R> {
+	type Output = &'a str;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<&'a str> {
+		loop {
+			let r = self
+				.reader
+				.as_mut()
+				.expect("NextLine polled after completion");
+			if r.current_index >= r.bytes_read {
+				let n = match Pin::new(&mut r.reader).poll_read(cx, &mut r.buf) {
+					Poll::Ready(n) => n.unwrap(),
+					Poll::Pending => return Poll::Pending,
+				};
+				r.bytes_read = n;
+				r.current_index = 0;
+				if n == 0 {
+					assert!(
+						!r.str_buf.is_empty(),
+						"AsyncInputReader: Reached end of input!"
+					);
+					break;
+				}
+			}
+
+			let window = &r.buf[r.current_index..r.bytes_read];
+			let end = window.iter().position(|&c| c == b'\n');
+			let len = end.unwrap_or(window.len());
+			r.str_buf.extend(window[..len].iter().map(|&c| c as char));
+			r.current_index += len;
+			if end.is_some() {
+				r.current_index += 1; // consume the newline
+				break;
+			}
+		}
+		let r = self.reader.take().unwrap();
+		r.line_done = true;
+		Poll::Ready(&r.str_buf)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::VecDeque;
+	use std::io;
+	use std::sync::Arc;
+	use std::task::{Wake, Waker};
+
+	// delivers one chunk per read, returning Pending before each of them
+	struct ChunkedReader {
+		chunks: VecDeque<&'static [u8]>,
+		ready: bool,
+	}
+
+	impl AsyncRead for ChunkedReader {
+		fn poll_read(
+			mut self: Pin<&mut Self>,
+			cx: &mut Context,
+			out: &mut [u8],
+		) -> Poll<io::Result<usize>> {
+			if !self.ready {
+				self.ready = true;
+				cx.waker().wake_by_ref();
+				return Poll::Pending;
+			}
+			self.ready = false;
+			let chunk = self.chunks.pop_front().unwrap_or(&[]);
+			out[..chunk.len()].copy_from_slice(chunk);
+			Poll::Ready(Ok(chunk.len()))
+		}
+	}
+
+	struct NoopWaker;
+
+	impl Wake for NoopWaker {
+		fn wake(self: Arc<Self>) {}
+	}
+
+	// polls `f` to completion, returning its output and how many times it was Pending
+	fn block_on<F: Future>(f: F) -> (F::Output, usize) {
+		let waker = Waker::from(Arc::new(NoopWaker));
+		let mut cx = Context::from_waker(&waker);
+		let mut f = Box::pin(f);
+		let mut pending = 0;
+		loop {
+			match f.as_mut().poll(&mut cx) {
+				Poll::Ready(out) => return (out, pending),
+				Poll::Pending => pending += 1,
+			}
+		}
+	}
+
+	#[test]
+	fn next_line_reassembles_chunks() {
+		let chunks = vec![&b"hel"[..], b"lo wo", b"rld\nnext\nlast"];
+		let mut input = AsyncInputReader::from_reader(ChunkedReader {
+			chunks: chunks.into_iter().collect(),
+			ready: false,
+		});
+		assert_eq!(block_on(input.next_line()), ("hello world", 3));
+		assert_eq!(block_on(input.next_line()), ("next", 0));
+		assert_eq!(block_on(input.next_line()), ("last", 1));
+	}
+
+	#[test]
+	fn next_line_survives_dropped_future() {
+		let chunks = vec![&b"hel"[..], b"lo\n", b"world\n"];
+		let mut input = AsyncInputReader::from_reader(ChunkedReader {
+			chunks: chunks.into_iter().collect(),
+			ready: false,
+		});
+		{
+			// poll until "hel" has been read, then drop the future like a timeout would
+			let waker = Waker::from(Arc::new(NoopWaker));
+			let mut cx = Context::from_waker(&waker);
+			let mut line = input.next_line();
+			assert!(Pin::new(&mut line).poll(&mut cx).is_pending());
+			assert!(Pin::new(&mut line).poll(&mut cx).is_pending());
+		}
+		assert_eq!(block_on(input.next_line()).0, "hello");
+		assert_eq!(block_on(input.next_line()).0, "world");
+	}
+}
@@ -7,8 +7,11 @@
   2019
 */
 
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, Read, Stdin};
+use std::io::{self, BufRead, Read, Stdin, Write};
+use std::path::{Path, PathBuf};
 
 pub struct InputReader<R: Read> {
 	reader: R,
@@ -16,6 +19,109 @@ pub struct InputReader<R: Read> {
 	bytes_read: usize,
 	current_index: usize,
 	str_buf: String,
+	max_line_len: usize,
+	line_overflow: LineOverflow,
+	strict: bool,
+	last_start: usize,
+	last_end: usize,
+	track_position: bool,
+	// newlines in the input already dropped from the buffer, and the bytes after the last one
+	lines_dropped: usize,
+	col_dropped: usize,
+	// what is being read, for error messages
+	op: &'static str,
+}
+
+// last_start when there is no token to unread, and last_end while a token is being read
+const NO_TOKEN: usize = usize::MAX;
+const IN_PROGRESS: usize = usize::MAX;
+
+/// what next_line does with a line longer than the maximum line length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOverflow {
+	Panic,
+	/// keep the first bytes of the line and skip the rest
+	Truncate,
+}
+
+/// kind of the next token, see peek_token_type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenType {
+	Integer,
+	Float,
+	Word,
+	Eof,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+	/// the number read was outside of the allowed range
+	OutOfRange(i64),
+	/// the token was not an optionally signed integer, e.g. a lone "-"
+	InvalidNumber(String),
+	/// the token did not match any of the expected options
+	NoMatch(String),
+}
+
+impl fmt::Display for ReadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ReadError::OutOfRange(n) => write!(f, "InputReader: {} is out of range", n),
+			ReadError::InvalidNumber(s) => write!(f, "InputReader: {:?} is not a valid integer", s),
+			ReadError::NoMatch(s) => write!(f, "InputReader: {:?} is not an expected option", s),
+		}
+	}
+}
+
+impl std::error::Error for ReadError {}
+
+/// a row of a grid read by next_grid_checked was not as wide as the first row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridError {
+	pub row: usize,
+	pub len: usize,
+	pub expected: usize,
+}
+
+impl fmt::Display for GridError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"InputReader: Grid row {} has length {}, expected {}",
+			self.row, self.len, self.expected
+		)
+	}
+}
+
+impl std::error::Error for GridError {}
+
+/// why InputReader::try_from_file could not open a file
+#[derive(Debug)]
+pub enum InputError {
+	NotFound(PathBuf),
+	PermissionDenied(PathBuf),
+	Other(io::Error),
+}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			InputError::NotFound(p) => write!(f, "InputReader: No such file {:?}", p),
+			InputError::PermissionDenied(p) => {
+				write!(f, "InputReader: Permission denied for {:?}", p)
+			}
+			InputError::Other(e) => write!(f, "InputReader: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for InputError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			InputError::Other(e) => Some(e),
+			_ => None,
+		}
+	}
 }
 
 impl InputReader<Stdin> {
@@ -24,10 +130,35 @@ impl InputReader<Stdin> {
 	}
 }
 
+impl Default for InputReader<Stdin> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl InputReader<File> {
-	pub fn from_file(path: impl AsRef<std::path::Path>) -> Self {
+	pub fn from_file(path: impl AsRef<Path>) -> Self {
 		Self::from_reader(File::open(path).unwrap())
 	}
+
+	pub fn try_from_file(path: impl AsRef<Path>) -> Result<Self, InputError> {
+		let path = path.as_ref();
+		match File::open(path) {
+			Ok(file) => Ok(Self::from_reader(file)),
+			Err(e) => Err(match e.kind() {
+				io::ErrorKind::NotFound => InputError::NotFound(path.to_path_buf()),
+				io::ErrorKind::PermissionDenied => InputError::PermissionDenied(path.to_path_buf()),
+				_ => InputError::Other(e),
+			}),
+		}
+	}
+}
+
+#[cfg(feature = "zstd")]
+impl InputReader<zstd::Decoder<'static, io::BufReader<File>>> {
+	pub fn from_zst_file(path: impl AsRef<Path>) -> Self {
+		Self::from_reader(zstd::Decoder::new(File::open(path).unwrap()).unwrap())
+	}
 }
 
 impl<R: Read> InputReader<R> {
@@ -38,18 +169,42 @@ impl<R: Read> InputReader<R> {
 			bytes_read: 0,
 			current_index: 0,
 			str_buf: String::with_capacity(1 << 8),
+			max_line_len: usize::MAX,
+			line_overflow: LineOverflow::Panic,
+			strict: false,
+			last_start: NO_TOKEN,
+			last_end: IN_PROGRESS,
+			track_position: false,
+			lines_dropped: 0,
+			col_dropped: 0,
+			op: "input",
 		}
 	}
 
 	pub fn next_word(&mut self) -> &str {
+		self.op = "word";
+		self.read_token();
+		&self.str_buf
+	}
+
+	/// like next_word, but only keeps the first `max` bytes of the word
+	pub fn next_word_capped(&mut self, max: usize) -> &str {
+		self.op = "word";
 		self.consume_until(|c| c.is_ascii_graphic());
 
 		self.str_buf.clear();
-		while self.peek().is_ascii_graphic() {
-			let c = self.peek();
-			self.str_buf.push(c);
-			self.consume();
-			if !self.has_more() {
+		while self.has_more() {
+			let window = &self.buf[self.current_index..self.bytes_read];
+			let len = window
+				.iter()
+				.position(|c| !c.is_ascii_graphic())
+				.unwrap_or(window.len());
+			let kept = len.min(max - self.str_buf.len());
+			// graphic ascii is always valid utf8
+			self.str_buf
+				.push_str(std::str::from_utf8(&window[..kept]).unwrap());
+			self.current_index += len;
+			if len < window.len() {
 				break;
 			}
 		}
@@ -57,20 +212,157 @@ impl<R: Read> InputReader<R> {
 	}
 
 	pub fn next_line(&mut self) -> &str {
-		self.str_buf.clear();
-		while self.peek() != '\n' {
-			let c = self.peek();
-			self.str_buf.push(c);
-			self.consume();
-			if !self.has_more() {
-				break;
+		self.op = "line";
+		self.read_line();
+		&self.str_buf
+	}
+
+	/// reads one line and iterates over its whitespace separated words
+	pub fn line_words(&mut self) -> impl Iterator<Item = &str> {
+		self.next_line().split_ascii_whitespace()
+	}
+
+	/// reads a `key = value` line, split on the first '=' with both sides trimmed
+	pub fn next_kv(&mut self) -> (String, String) {
+		self.op = "key=value";
+		self.read_line();
+		let line = &self.str_buf;
+		match line.find('=') {
+			Some(i) => (
+				line[..i].trim().to_string(),
+				line[i + 1..].trim().to_string(),
+			),
+			None => self.fail(format_args!("Expected key=value, got {:?}", line)),
+		}
+	}
+
+	/// reads all remaining non-blank lines with next_kv
+	pub fn read_all_kv(&mut self) -> HashMap<String, String> {
+		self.op = "key=value";
+		let mut map = HashMap::new();
+		while self.has_more() {
+			if self.peek().is_ascii_whitespace() {
+				self.consume();
+				continue;
 			}
+			let (k, v) = self.next_kv();
+			map.insert(k, v);
+		}
+		map
+	}
+
+	/// reads the next word as padded standard base64 and decodes it
+	pub fn next_base64(&mut self) -> Vec<u8> {
+		self.op = "base64";
+		self.read_token();
+		let token = self.str_buf.as_bytes();
+		let padding = token.iter().rev().take_while(|&&c| c == b'=').count();
+		if !(token.len() % 4 == 0
+			&& padding <= 2
+			&& !token[..token.len() - padding].contains(&b'='))
+		{
+			self.fail(format_args!("Invalid base64 padding"));
+		}
+
+		let mut bytes = Vec::with_capacity(token.len() / 4 * 3);
+		for chunk in token.chunks(4) {
+			let mut n = 0;
+			for &c in chunk {
+				let v = match c {
+					b'A'..=b'Z' => c - b'A',
+					b'a'..=b'z' => c - b'a' + 26,
+					b'0'..=b'9' => c - b'0' + 52,
+					b'+' => 62,
+					b'/' => 63,
+					b'=' => 0,
+					_ => self.fail(format_args!("Invalid base64 character {:?}", c as char)),
+				};
+				n = n << 6 | v as u32;
+			}
+			bytes.extend(&[(n >> 16) as u8, (n >> 8) as u8, n as u8]);
+		}
+		bytes.truncate(bytes.len() - padding);
+		bytes
+	}
+
+	/// reads the next word and returns the index of the option it equals
+	pub fn try_expect_one_of(&mut self, options: &[&str]) -> Result<usize, ReadError> {
+		self.op = "option";
+		self.read_token();
+		let token = self.str_buf.as_str();
+		options
+			.iter()
+			.position(|&o| o == token)
+			.ok_or_else(|| ReadError::NoMatch(token.to_string()))
+	}
+
+	pub fn expect_one_of(&mut self, options: &[&str]) -> usize {
+		match self.try_expect_one_of(options) {
+			Ok(i) => i,
+			Err(_) => self.fail(format_args!(
+				"Expected one of {:?}, got {:?}",
+				options, self.str_buf
+			)),
 		}
-		self.consume(); // consume the newline
-		&self.str_buf
 	}
 
+	/// reads an "HH:MM:SS" or "MM:SS" duration as a number of seconds. Hours may exceed 23
+	pub fn next_time_hms(&mut self) -> u64 {
+		self.op = "time";
+		self.read_token();
+		let token = &self.str_buf;
+		let invalid = || -> ! { self.fail(format_args!("Invalid time {:?}", token)) };
+		let fields: Vec<u64> = token
+			.split(':')
+			.map(|f| {
+				if f.is_empty() || !f.bytes().all(|c| c.is_ascii_digit()) {
+					invalid();
+				}
+				f.parse().unwrap_or_else(|_| invalid())
+			})
+			.collect();
+		if !((fields.len() == 2 || fields.len() == 3) && fields[1..].iter().all(|&f| f < 60)) {
+			invalid();
+		}
+		fields
+			.iter()
+			.try_fold(0u64, |secs, &f| secs.checked_mul(60)?.checked_add(f))
+			.unwrap_or_else(|| invalid())
+	}
+
+	/// reads a complex number like "3+4i", "-2i", "i" or "5" as (real, imag)
+	pub fn next_complex(&mut self) -> (f64, f64) {
+		self.op = "complex number";
+		self.read_token();
+		let token = self.str_buf.as_str();
+		let num = |s: &str| -> f64 {
+			s.parse()
+				.unwrap_or_else(|_| self.fail(format_args!("Invalid complex number {:?}", token)))
+		};
+		let coefficient = |s: &str| match s {
+			"" | "+" => 1.0,
+			"-" => -1.0,
+			_ => num(s),
+		};
+
+		let body = match token.strip_suffix('i') {
+			Some(body) => body,
+			None => return (num(token), 0.0),
+		};
+		let b = body.as_bytes();
+		let split = (1..b.len())
+			.rev()
+			.find(|&k| (b[k] == b'+' || b[k] == b'-') && b[k - 1] != b'e' && b[k - 1] != b'E');
+		match split {
+			Some(k) => (num(&body[..k]), coefficient(&body[k..])),
+			None => (0.0, coefficient(body)),
+		}
+	}
+
+	/// only the char itself is consumed, so reading the last byte of the input
+	/// returns it and has_more reports false afterwards
 	pub fn next_char(&mut self) -> char {
+		self.op = "char";
 		self.consume_until(|c| c.is_ascii_graphic());
 
 		let c = self.peek();
@@ -79,40 +371,326 @@ impl<R: Read> InputReader<R> {
 	}
 
 	pub fn next_u64(&mut self) -> u64 {
-		self.consume_until(|c| c.is_ascii_digit());
+		self.op = "u64";
+		self.read_u64()
+	}
 
+	/// calls `f` with every remaining unsigned integer in the input, without allocating
+	pub fn for_each_u64<F: FnMut(u64)>(&mut self, mut f: F) {
+		self.op = "u64";
+		if self.strict {
+			loop {
+				while self.has_more() && self.peek().is_ascii_whitespace() {
+					self.consume();
+				}
+				if !self.has_more() {
+					return;
+				}
+				f(self.read_u64());
+			}
+		}
 		let mut num = 0;
-		while self.peek().is_ascii_digit() {
-			let digit = self.peek() as u64 - '0' as u64;
-			num = num * 10 + digit;
-			self.consume();
-			if !self.has_more() {
-				break;
+		let mut in_num = false;
+		while self.has_more() {
+			for &c in &self.buf[self.current_index..self.bytes_read] {
+				if c.is_ascii_digit() {
+					num = num * 10 + (c - b'0') as u64;
+					in_num = true;
+				} else if in_num {
+					f(num);
+					num = 0;
+					in_num = false;
+				}
 			}
+			self.current_index = self.bytes_read;
+		}
+		if in_num {
+			f(num);
 		}
-		num
 	}
 
 	pub fn next_i64(&mut self) -> i64 {
-		let sign = self.consume_until_signed_num();
-		self.next_u64() as i64 * sign
+		self.op = "i64";
+		let sign = self.consume_until_signed_num(false);
+		let num = self.read_digits() as i64;
+		self.last_end = self.current_index;
+		num.wrapping_mul(sign)
+	}
+
+	/// strict version of next_i64: the whole next token must be an optionally
+	/// signed integer, so "007" is 7 and "-00" or "+0" are 0 but "-" or "5x" are errors
+	pub fn try_next_i64(&mut self) -> Result<i64, ReadError> {
+		self.op = "i64";
+		self.read_token();
+		let token = self.str_buf.as_str();
+		let (negative, digits) = match token.as_bytes().first() {
+			Some(b'-') => (true, &token[1..]),
+			Some(b'+') => (false, &token[1..]),
+			_ => (false, token),
+		};
+		let invalid = || ReadError::InvalidNumber(token.to_string());
+		if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+			return Err(invalid());
+		}
+
+		let n = digits.bytes().try_fold(0u64, |n, c| {
+			n.checked_mul(10)?.checked_add((c - b'0') as u64)
+		});
+		match n {
+			Some(n) if negative && n <= i64::MAX as u64 + 1 => Ok((n as i64).wrapping_neg()),
+			Some(n) if !negative && n <= i64::MAX as u64 => Ok(n as i64),
+			_ => Err(invalid()),
+		}
+	}
+
+	/// reads the next i64 like try_next_i64, returning an error if it is not within lo..=hi
+	/// only the token itself is consumed, so the caller can retry on failure
+	pub fn try_next_bounded_i64(&mut self, lo: i64, hi: i64) -> Result<i64, ReadError> {
+		let n = self.try_next_i64()?;
+		if lo <= n && n <= hi {
+			Ok(n)
+		} else {
+			Err(ReadError::OutOfRange(n))
+		}
 	}
 
 	pub fn next_f64(&mut self) -> f64 {
-		let sign = self.consume_until_signed_num() as f64;
-		let num: f64 = self.next_word().parse().unwrap();
+		self.op = "f64";
+		let sign = self.consume_until_signed_num(true) as f64;
+		self.read_word();
+		self.last_end = self.current_index;
+		let num: f64 = match self.str_buf.parse() {
+			Ok(num) => num,
+			Err(_) => self.fail(format_args!("Invalid number {:?}", self.str_buf)),
+		};
 		num * sign
 	}
 
+	/// reads a non-negative decimal integer of any length as little-endian limbs in the given base
+	pub fn next_bigint_limbs(&mut self, base: u64) -> Vec<u64> {
+		self.op = "bigint";
+		if base < 2 {
+			self.fail(format_args!("Invalid base {} for bigint limbs", base));
+		}
+		self.read_token();
+		let digits = self.str_buf.as_bytes();
+		if !digits.iter().all(|c| c.is_ascii_digit()) {
+			self.fail(format_args!("Invalid digit in bigint"));
+		}
+
+		// 18 decimal digits at a time always fit in a u64
+		let mut limbs = vec![0u64];
+		for chunk in digits.chunks(18) {
+			let mul = 10u128.pow(chunk.len() as u32);
+			let mut carry = chunk
+				.iter()
+				.fold(0u128, |acc, &c| acc * 10 + (c - b'0') as u128);
+			for limb in limbs.iter_mut() {
+				let x = *limb as u128 * mul + carry;
+				*limb = (x % base as u128) as u64;
+				carry = x / base as u128;
+			}
+			while carry > 0 {
+				limbs.push((carry % base as u128) as u64);
+				carry /= base as u128;
+			}
+		}
+		limbs
+	}
+
+	/// reads a permutation of 0..n, given 1-indexed in the input if `one_indexed` is set
+	pub fn next_permutation(&mut self, n: usize, one_indexed: bool) -> Vec<usize> {
+		self.op = "permutation";
+		let offset = one_indexed as usize;
+		let mut seen = vec![false; n];
+		(0..n)
+			.map(|_| {
+				let x = self.read_u64() as usize;
+				if x < offset || x - offset >= n {
+					self.fail(format_args!(
+						"{} is out of range for a permutation of size {}",
+						x, n
+					));
+				}
+				if seen[x - offset] {
+					self.fail(format_args!("{} appears twice in permutation", x));
+				}
+				seen[x - offset] = true;
+				x - offset
+			})
+			.collect()
+	}
+
+	/// classifies the next token without consuming it, only the whitespace before it
+	pub fn peek_token_type(&mut self) -> TokenType {
+		self.op = "token";
+		while self.has_more() && !self.peek().is_ascii_graphic() {
+			self.consume();
+		}
+		if !self.has_more() {
+			return TokenType::Eof;
+		}
+
+		let mut len = 0;
+		while self.fill_ahead(len + 1) > len
+			&& self.buf[self.current_index + len].is_ascii_graphic()
+		{
+			len += 1;
+		}
+		classify_token(&self.buf[self.current_index..self.current_index + len])
+	}
+
+	/// reads an n x n 0/1 matrix, with rows written either as "0 1 0" or as "010".
+	/// The format is detected from the first row
+	pub fn next_adjacency_matrix(&mut self, n: usize) -> Vec<Vec<bool>> {
+		self.op = "adjacency matrix";
+		self.consume_until(|c| c.is_ascii_graphic());
+		let mut separated = None;
+		(0..n)
+			.map(|_| {
+				self.read_line();
+				let line = self.str_buf.trim();
+				let separated =
+					*separated.get_or_insert_with(|| line.contains(char::is_whitespace));
+				let invalid = |entry: &dyn fmt::Debug| -> ! {
+					self.fail(format_args!("Invalid adjacency matrix entry {:?}", entry))
+				};
+				let row: Vec<bool> = if separated {
+					line.split_ascii_whitespace()
+						.map(|t| match t.as_bytes() {
+							&[c] => adjacency_entry(c).unwrap_or_else(|| invalid(&t)),
+							_ => invalid(&t),
+						})
+						.collect()
+				} else {
+					line.bytes()
+						.map(|c| adjacency_entry(c).unwrap_or_else(|| invalid(&(c as char))))
+						.collect()
+				};
+				if row.len() != n {
+					self.fail(format_args!(
+						"Row has {} entries, expected {}",
+						row.len(),
+						n
+					));
+				}
+				row
+			})
+			.collect()
+	}
+
+	/// reads `rows` lines as a grid of chars, checking that all rows are equally wide.
+	/// Line breaks before the first row are skipped
+	pub fn next_grid_checked(&mut self, rows: usize) -> Result<Vec<Vec<char>>, GridError> {
+		if rows == 0 {
+			return Ok(Vec::new());
+		}
+		self.op = "grid";
+		self.consume_until(|c| c != '\n' && c != '\r');
+		let mut grid: Vec<Vec<char>> = Vec::with_capacity(rows);
+		for row in 0..rows {
+			self.read_line();
+			let line: Vec<char> = self.str_buf.trim_end_matches('\r').chars().collect();
+			if let Some(first) = grid.first() {
+				if line.len() != first.len() {
+					return Err(GridError {
+						row,
+						len: line.len(),
+						expected: first.len(),
+					});
+				}
+			}
+			grid.push(line);
+		}
+		Ok(grid)
+	}
+
 	pub fn has_more(&mut self) -> bool {
 		if self.current_index >= self.bytes_read {
-			self.bytes_read = self.reader.read(&mut self.buf[..]).unwrap();
-			self.current_index = 0
+			self.refill();
+		}
+		self.current_index < self.bytes_read
+	}
+
+	/// pushes back the last token read by next_word or one of the number readers, so the
+	/// next read returns it again. Only one token can be pushed back, only directly after
+	/// it was read, and only if it is shorter than the buffer size
+	pub fn unread_last(&mut self) {
+		// a misuse of the reader rather than bad input, so there is no operation to report
+		assert!(
+			self.last_start != NO_TOKEN && self.last_end == self.current_index,
+			"InputReader: No token to unread!"
+		);
+		self.current_index = self.last_start;
+		self.last_start = NO_TOKEN;
+	}
+
+	/// appends bytes to `out` up to and including the first occurrence of `seq`.
+	/// Returns false if the input ends before `seq` is found
+	pub fn read_until_seq(&mut self, seq: &[u8], out: &mut Vec<u8>) -> bool {
+		self.op = "sequence";
+		let start = out.len();
+		let last = match seq.last() {
+			Some(&c) => c,
+			None => return true,
+		};
+		while self.has_more() {
+			let window = &self.buf[self.current_index..self.bytes_read];
+			for (i, &c) in window.iter().enumerate() {
+				out.push(c);
+				if c == last && out.len() - start >= seq.len() && out.ends_with(seq) {
+					self.current_index += i + 1;
+					return true;
+				}
+			}
+			self.current_index = self.bytes_read;
 		}
-		self.bytes_read > 0
+		false
+	}
+
+	/// counts the '\n' bytes in the rest of the input without moving the cursor.
+	/// To do so, all of the remaining input is read into the buffer
+	pub fn count_lines(&mut self) -> usize {
+		self.op = "line count";
+		let available = self.fill_ahead(usize::MAX);
+		let start = self.current_index.min(self.bytes_read);
+		count_newlines(&self.buf[start..start + available])
+	}
+
+	/// copies everything not yet read, buffered or not, to `out`. Returns the number of bytes copied
+	pub fn drain_to<W: Write>(&mut self, out: &mut W) -> io::Result<u64> {
+		let start = self.current_index.min(self.bytes_read);
+		out.write_all(&self.buf[start..self.bytes_read])?;
+		self.current_index = self.bytes_read;
+		let copied = io::copy(&mut self.reader, out)?;
+		Ok((self.bytes_read - start) as u64 + copied)
+	}
+
+	/// std BufRead view of the rest of the stream, sharing the reader's buffer and position
+	pub fn as_bufread(&mut self) -> impl BufRead + '_ {
+		BufReadView { reader: self }
+	}
+
+	/// limits how many bytes of a line next_line will store. Default: unlimited
+	pub fn set_max_line_len(&mut self, max: usize, on_overflow: LineOverflow) {
+		self.max_line_len = max;
+		self.line_overflow = on_overflow;
+	}
+
+	/// include the line and column in panic messages. This costs a pass over the input to
+	/// count newlines, and positions are counted from where tracking was enabled. Default: false
+	pub fn set_track_position(&mut self, track: bool) {
+		self.track_position = track;
+	}
+
+	/// in strict mode, number readers panic on anything but whitespace before a number
+	/// instead of skipping it. Default: false
+	pub fn set_strict(&mut self, strict: bool) {
+		self.strict = strict;
 	}
 
 	pub fn set_buf_size(&mut self, buf_size: usize) {
+		assert!(buf_size > 0, "InputReader: Buffer size must be non-zero!");
 		assert!(
 			buf_size >= self.bytes_read,
 			"InputReader: Data loss while shrinking buffer!"
@@ -158,26 +736,1220 @@ impl<R: Read> InputReader<R> {
 	}
 
 	fn assert_has_more(&mut self) {
-		assert!(self.has_more(), "InputReader: Reached end of input!");
+		if !self.has_more() {
+			self.fail(format_args!("Reached end of input"));
+		}
 	}
 
-	fn consume_until<F: Fn(char) -> bool>(&mut self, test: F) {
-		while !test(self.peek()) {
+	fn refill(&mut self) {
+		if let Err(e) = self.try_refill() {
+			self.fail(format_args!("Read error ({})", e));
+		}
+	}
+
+	// reads more input after the buffered bytes that still have to be kept
+	fn try_refill(&mut self) -> io::Result<()> {
+		let keep = self.keep_from();
+		self.compact(keep);
+		if self.bytes_read == self.buf.len() {
+			let len = self.buf.len();
+			self.buf.resize(len * 2, 0);
+		}
+		self.bytes_read += self.reader.read(&mut self.buf[self.bytes_read..])?;
+		Ok(())
+	}
+
+	// start of the bytes a refill must keep: the cursor, or the last token while it can be
+	// unread. A token that no longer fits in the buffer is given up instead of growing it
+	fn keep_from(&self) -> usize {
+		let start = self.current_index.min(self.bytes_read);
+		if self.can_unread()
+			&& self.last_start < start
+			&& self.bytes_read - self.last_start < self.buf.len()
+		{
+			self.last_start
+		} else {
+			start
+		}
+	}
+
+	fn can_unread(&self) -> bool {
+		self.last_end == IN_PROGRESS || self.last_end == self.current_index
+	}
+
+	// moves the buffered bytes from `from` on to the front of the buffer
+	fn compact(&mut self, from: usize) {
+		let end = self.bytes_read;
+		let keep_token = self.can_unread() && (from..=end).contains(&self.last_start);
+		if self.track_position {
+			let dropped = &self.buf[..from];
+			match count_newlines(dropped) {
+				0 => self.col_dropped += from,
+				n => {
+					self.lines_dropped += n;
+					self.col_dropped = dropped.iter().rev().take_while(|&&c| c != b'\n').count();
+				}
+			}
+		}
+		self.buf.copy_within(from..end, 0);
+		self.current_index = self.current_index.min(end) - from;
+		self.bytes_read = end - from;
+		if !keep_token {
+			self.last_start = NO_TOKEN;
+		} else {
+			self.last_start -= from;
+			if self.last_end != IN_PROGRESS {
+				self.last_end = self.current_index;
+			}
+		}
+	}
+
+	// 1-based line and column of the cursor
+	fn position(&self) -> (usize, usize) {
+		let before = &self.buf[..self.current_index.min(self.bytes_read)];
+		match before.iter().rposition(|&c| c == b'\n') {
+			Some(i) => (
+				self.lines_dropped + count_newlines(before) + 1,
+				before.len() - i,
+			),
+			None => (self.lines_dropped + 1, self.col_dropped + before.len() + 1),
+		}
+	}
+
+	// panics with what was being read, and where if positions are tracked. Kept out of
+	// line so the panic formatting does not slow down the readers calling it
+	#[cold]
+	#[inline(never)]
+	fn fail(&self, msg: fmt::Arguments) -> ! {
+		if !self.track_position {
+			panic!("InputReader: {} while reading {}!", msg, self.op);
+		}
+		let (line, col) = self.position();
+		panic!(
+			"InputReader: {} while reading {} at line {}, col {}!",
+			msg, self.op, line, col
+		)
+	}
+
+	// reads the next word into str_buf, so that it can be unread
+	fn read_token(&mut self) {
+		self.consume_until(|c| c.is_ascii_graphic());
+		self.begin_token();
+		self.read_word();
+		self.last_end = self.current_index;
+	}
+
+	fn read_line(&mut self) {
+		self.str_buf.clear();
+		while self.peek() != '\n' {
+			if self.str_buf.len() < self.max_line_len {
+				let c = self.peek();
+				self.str_buf.push(c);
+			} else if self.line_overflow == LineOverflow::Panic {
+				self.fail(format_args!("Line exceeds the maximum line length"));
+			}
 			self.consume();
+			if !self.has_more() {
+				break;
+			}
+		}
+		self.consume(); // consume the newline
+	}
+
+	fn read_u64(&mut self) -> u64 {
+		self.consume_until_num();
+		self.begin_token();
+		let num = self.read_digits();
+		self.last_end = self.current_index;
+		num
+	}
+
+	fn begin_token(&mut self) {
+		self.last_start = self.current_index;
+		self.last_end = IN_PROGRESS;
+	}
+
+	// makes sure n bytes after the cursor are buffered, compacting and growing the buffer
+	// if needed. Returns the number of buffered bytes, which is less than n only at EOF
+	fn fill_ahead(&mut self, n: usize) -> usize {
+		loop {
+			let start = self.current_index.min(self.bytes_read);
+			let available = self.bytes_read - start;
+			if available >= n {
+				return available;
+			}
+
+			let before = self.bytes_read - self.keep_from();
+			self.refill();
+			if self.bytes_read == before {
+				return available;
+			}
+		}
+	}
+
+	// reads the word starting at the cursor into str_buf
+	fn read_word(&mut self) {
+		self.str_buf.clear();
+		while self.has_more() {
+			let window = &self.buf[self.current_index..self.bytes_read];
+			let mut len = 0;
+			if self.str_buf.is_empty() {
+				for &c in window {
+					if !c.is_ascii_graphic() {
+						break;
+					}
+					self.str_buf.push(c as char);
+					len += 1;
+				}
+			} else {
+				// the word is longer than a buffer window, so copy the rest in bulk
+				len = window
+					.iter()
+					.position(|c| !c.is_ascii_graphic())
+					.unwrap_or(window.len());
+				// graphic ascii is always valid utf8
+				self.str_buf
+					.push_str(std::str::from_utf8(&window[..len]).unwrap());
+			}
+			self.current_index += len;
+			if len < window.len() {
+				break;
+			}
+		}
+	}
+
+	// reads the digits starting at the cursor
+	fn read_digits(&mut self) -> u64 {
+		let mut num = 0;
+		while self.has_more() {
+			let window = &self.buf[self.current_index..self.bytes_read];
+			let mut len = 0;
+			for &c in window {
+				if !c.is_ascii_digit() {
+					break;
+				}
+				num = num * 10 + (c - b'0') as u64;
+				len += 1;
+			}
+			self.current_index += len;
+			if len < window.len() {
+				break;
+			}
 		}
+		num
 	}
 
-	fn consume_until_signed_num(&mut self) -> i64 {
+	fn consume_until<F: Fn(char) -> bool>(&mut self, test: F) {
 		loop {
-			self.consume_until(|c| c.is_ascii_digit() || c == '-');
-			if self.peek() != '-' {
-				return 1;
+			self.assert_has_more();
+			let window = &self.buf[self.current_index..self.bytes_read];
+			match window.iter().position(|&c| test(c as char)) {
+				Some(i) => return self.current_index += i,
+				None => self.current_index = self.bytes_read,
 			}
+		}
+	}
+
+	// skips to the next digit, or in strict mode to the next non-whitespace byte,
+	// which then has to be a digit or a '+' followed by one
+	fn consume_until_num(&mut self) {
+		if self.strict {
+			self.consume_until(|c| !c.is_ascii_whitespace());
+			if self.peek() == '+' {
+				self.consume();
+			}
+			self.assert_digit();
+		} else {
+			self.consume_until(|c| c.is_ascii_digit());
+		}
+	}
+
+	fn assert_digit(&mut self) {
+		let c = self.peek();
+		if !c.is_ascii_digit() {
+			self.fail(format_args!("Expected a number, found {:?}", c));
+		}
+	}
+
+	// skips to the next number and returns its sign. A sign not directly
+	// followed by a digit, like a lone "-" or "+", is skipped like any other junk.
+	// With `frac`, the number may also start at its decimal point, as in ".5"
+	fn consume_until_signed_num(&mut self, frac: bool) -> i64 {
+		if self.strict {
+			self.consume_until(|c| !c.is_ascii_whitespace());
+			self.begin_token();
+			let sign = match self.peek() {
+				'-' => -1,
+				_ => 1,
+			};
+			if let '-' | '+' = self.peek() {
+				self.consume();
+			}
+			if !(frac && self.at_fraction()) {
+				self.assert_digit();
+			}
+			return sign;
+		}
+
+		loop {
+			self.consume_until(|c| {
+				c.is_ascii_digit() || c == '-' || c == '+' || (frac && c == '.')
+			});
+			self.begin_token();
+			let sign = match self.peek() {
+				'-' => -1,
+				'+' => 1,
+				'.' if self.at_fraction() => return 1,
+				'.' => {
+					self.consume();
+					continue;
+				}
+				_ => return 1,
+			};
 
 			self.consume();
-			if self.peek().is_ascii_digit() {
-				return -1;
+			if self.peek().is_ascii_digit() || (frac && self.at_fraction()) {
+				return sign;
 			}
 		}
 	}
+
+	// whether the cursor is at a decimal point followed by a digit
+	fn at_fraction(&mut self) -> bool {
+		self.fill_ahead(2) >= 2
+			&& self.buf[self.current_index] == b'.'
+			&& self.buf[self.current_index + 1].is_ascii_digit()
+	}
+}
+
+// counts '\n' bytes 8 at a time, https://graphics.stanford.edu/~seander/bithacks.html#ZeroInWord
+fn count_newlines(bytes: &[u8]) -> usize {
+	const LO7: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+	const NL: u64 = 0x0a0a_0a0a_0a0a_0a0a;
+	let chunks = bytes.chunks_exact(8);
+	let tail = chunks.remainder().iter().filter(|&&c| c == b'\n').count();
+	chunks.fold(tail, |count, chunk| {
+		let mut word = [0; 8];
+		word.copy_from_slice(chunk);
+		let x = u64::from_ne_bytes(word) ^ NL;
+		let zero_bytes = !(((x & LO7) + LO7) | x | LO7);
+		count + zero_bytes.count_ones() as usize
+	})
+}
+
+fn adjacency_entry(c: u8) -> Option<bool> {
+	match c {
+		b'0' => Some(false),
+		b'1' => Some(true),
+		_ => None,
+	}
+}
+
+fn classify_token(token: &[u8]) -> TokenType {
+	fn digits(s: &[u8]) -> usize {
+		s.iter().take_while(|c| c.is_ascii_digit()).count()
+	}
+	fn sign(s: &[u8]) -> usize {
+		match s.first() {
+			Some(b'+') | Some(b'-') => 1,
+			_ => 0,
+		}
+	}
+
+	let mut i = sign(token);
+	let int_digits = digits(&token[i..]);
+	i += int_digits;
+	if int_digits > 0 && i == token.len() {
+		return TokenType::Integer;
+	}
+
+	let mut frac_digits = 0;
+	if token.get(i) == Some(&b'.') {
+		i += 1;
+		frac_digits = digits(&token[i..]);
+		i += frac_digits;
+	}
+	if int_digits + frac_digits == 0 {
+		return TokenType::Word;
+	}
+	if let Some(b'e') | Some(b'E') = token.get(i) {
+		i += 1;
+		i += sign(&token[i..]);
+		let exp_digits = digits(&token[i..]);
+		if exp_digits == 0 {
+			return TokenType::Word;
+		}
+		i += exp_digits;
+	}
+	if i == token.len() {
+		TokenType::Float
+	} else {
+		TokenType::Word
+	}
+}
+
+struct BufReadView<'a, R: Read> {
+	reader: &'a mut InputReader<R>,
+}
+
+impl<'a, R: Read> Read for BufReadView<'a, R> {
+	fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+		let n = {
+			let available = self.fill_buf()?;
+			let n = available.len().min(out.len());
+			out[..n].copy_from_slice(&available[..n]);
+			n
+		};
+		self.consume(n);
+		Ok(n)
+	}
+}
+
+impl<'a, R: Read> BufRead for BufReadView<'a, R> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		let r = &mut *self.reader;
+		if r.current_index >= r.bytes_read {
+			r.try_refill()?;
+		}
+		Ok(&r.buf[r.current_index..r.bytes_read])
+	}
+
+	fn consume(&mut self, amt: usize) {
+		self.reader.current_index += amt;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate csv;
+
+	use super::*;
+
+	fn reader(input: &str) -> InputReader<&[u8]> {
+		InputReader::from_reader(input.as_bytes())
+	}
+
+	#[test]
+	fn next_word_capped_truncates() {
+		let mut input = reader("abcdefgh ij");
+		assert_eq!(input.next_word_capped(3), "abc");
+		assert_eq!(input.next_word(), "ij");
+	}
+
+	#[test]
+	fn next_word_capped_across_buffer() {
+		let mut input = reader("abcdefghijkl 42");
+		input.set_buf_size(4);
+		assert_eq!(input.next_word_capped(6), "abcdef");
+		assert_eq!(input.next_u64(), 42);
+		assert!(!input.has_more());
+	}
+
+	#[test]
+	fn next_bigint_limbs_small() {
+		let mut input = reader("0 4294967296 255");
+		assert_eq!(input.next_bigint_limbs(1 << 32), vec![0]);
+		assert_eq!(input.next_bigint_limbs(1 << 32), vec![0, 1]);
+		assert_eq!(input.next_bigint_limbs(2), vec![1; 8]);
+	}
+
+	#[test]
+	fn next_bigint_limbs_200_digits() {
+		let digits: String = "1234567890".repeat(20);
+		let mut input = reader(&digits);
+		let base = 1u64 << 32;
+		let mut limbs = input.next_bigint_limbs(base);
+		assert!(limbs.iter().all(|&l| l < base));
+
+		// convert back to decimal by repeatedly dividing the limbs by 10
+		let mut decimal = Vec::new();
+		while limbs.iter().any(|&l| l != 0) {
+			let mut rem = 0;
+			for limb in limbs.iter_mut().rev() {
+				let x = rem * base + *limb;
+				*limb = x / 10;
+				rem = x % 10;
+			}
+			decimal.push(b'0' + rem as u8);
+		}
+		decimal.reverse();
+		assert_eq!(String::from_utf8(decimal).unwrap(), digits);
+	}
+
+	struct FailingReader;
+
+	impl Read for FailingReader {
+		fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+			Err(io::ErrorKind::BrokenPipe.into())
+		}
+	}
+
+	#[test]
+	fn as_bufread_continues_after_reader() {
+		let mut input = reader("2\nname,age\nalice,30\nbob,25\n");
+		input.set_buf_size(8);
+		assert_eq!(input.next_usize(), 2);
+		input.next_line();
+
+		let rows: Vec<(String, u32)> = csv::Reader::from_reader(input.as_bufread())
+			.deserialize()
+			.map(|r| r.unwrap())
+			.collect();
+		assert_eq!(
+			rows,
+			vec![("alice".to_string(), 30), ("bob".to_string(), 25)]
+		);
+		assert!(!input.has_more());
+	}
+
+	#[test]
+	fn as_bufread_shares_position() {
+		let mut input = reader("header\nline 1\nline 2\n3");
+		input.next_line();
+		let lines: Vec<String> = input
+			.as_bufread()
+			.lines()
+			.take(2)
+			.map(|l| l.unwrap())
+			.collect();
+		assert_eq!(lines, ["line 1", "line 2"]);
+		assert_eq!(input.next_u64(), 3);
+	}
+
+	#[test]
+	fn as_bufread_returns_read_errors() {
+		let mut input = InputReader::from_reader(FailingReader);
+		let err = input.as_bufread().fill_buf().unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+	}
+
+	#[test]
+	#[should_panic(expected = "Buffer size must be non-zero")]
+	fn set_buf_size_rejects_zero() {
+		reader("1").set_buf_size(0);
+	}
+
+	#[test]
+	fn set_buf_size_keeps_buffered_input() {
+		let mut input = reader("12 34");
+		assert_eq!(input.next_u64(), 12);
+		input.set_buf_size(5);
+		assert_eq!(input.next_u64(), 34);
+	}
+
+	#[cfg(feature = "zstd")]
+	#[test]
+	fn from_zst_file() {
+		let path = std::env::temp_dir().join(format!("easy_io_{}.zst", std::process::id()));
+		let compressed = zstd::encode_all("3\nhello 42\n".as_bytes(), 0).unwrap();
+		std::fs::write(&path, compressed).unwrap();
+
+		let mut input = InputReader::from_zst_file(&path);
+		assert_eq!(input.next_usize(), 3);
+		assert_eq!(input.next_word(), "hello");
+		assert_eq!(input.next_u64(), 42);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn try_next_bounded_i64_retries() {
+		let mut input = reader("150 -5x 50");
+		assert_eq!(
+			input.try_next_bounded_i64(0, 100),
+			Err(ReadError::OutOfRange(150))
+		);
+		assert_eq!(
+			input.try_next_bounded_i64(0, 100),
+			Err(ReadError::InvalidNumber("-5x".to_string()))
+		);
+		assert_eq!(input.try_next_bounded_i64(0, 100), Ok(50));
+	}
+
+	#[test]
+	fn try_next_bounded_i64_reread() {
+		let mut input = reader("150 7");
+		assert!(input.try_next_bounded_i64(0, 100).is_err());
+		input.unread_last();
+		assert_eq!(input.next_i64(), 150);
+		assert_eq!(input.next_i64(), 7);
+	}
+
+	#[test]
+	fn max_line_len_truncates() {
+		let input = format!("{}\nnext\n", "x".repeat(10 << 20));
+		let mut input = reader(&input);
+		input.set_max_line_len(1 << 20, LineOverflow::Truncate);
+		let line = input.next_line();
+		assert_eq!(line.len(), 1 << 20);
+		assert!(line.bytes().all(|c| c == b'x'));
+		assert_eq!(input.next_line(), "next");
+	}
+
+	#[test]
+	#[should_panic(expected = "Line exceeds the maximum line length while reading line")]
+	fn max_line_len_panics() {
+		let input = format!("{}\n", "x".repeat(10 << 20));
+		let mut input = reader(&input);
+		input.set_max_line_len(1 << 20, LineOverflow::Panic);
+		input.next_line();
+	}
+
+	#[test]
+	fn next_permutation() {
+		let mut input = reader("3 1 2\n2 0 1");
+		assert_eq!(input.next_permutation(3, true), vec![2, 0, 1]);
+		assert_eq!(input.next_permutation(3, false), vec![2, 0, 1]);
+	}
+
+	#[test]
+	#[should_panic(expected = "2 appears twice in permutation while reading permutation")]
+	fn next_permutation_duplicate() {
+		reader("1 2 2").next_permutation(3, true);
+	}
+
+	#[test]
+	#[should_panic(expected = "0 is out of range for a permutation of size 3")]
+	fn next_permutation_out_of_range() {
+		reader("0 1 2").next_permutation(3, true);
+	}
+
+	#[test]
+	fn next_kv() {
+		let mut input = reader("n = 5\nseed=42\nexpr = a=b\n");
+		assert_eq!(input.next_kv(), ("n".to_string(), "5".to_string()));
+		assert_eq!(input.next_kv(), ("seed".to_string(), "42".to_string()));
+		assert_eq!(input.next_kv(), ("expr".to_string(), "a=b".to_string()));
+	}
+
+	#[test]
+	fn read_all_kv() {
+		let map = reader("n = 5\n\n  seed=42\n").read_all_kv();
+		assert_eq!(map.len(), 2);
+		assert_eq!(map["n"], "5");
+		assert_eq!(map["seed"], "42");
+	}
+
+	#[test]
+	#[should_panic(expected = "Expected key=value, got \"oops\" while reading key=value")]
+	fn next_kv_without_equals() {
+		reader("oops\n").next_kv();
+	}
+
+	#[test]
+	fn for_each_u64_across_buffer() {
+		let mut input = reader("12345 6 x78-9\n1234567890123");
+		input.set_buf_size(4);
+		let mut nums = Vec::new();
+		input.for_each_u64(|x| nums.push(x));
+		assert_eq!(nums, [12345, 6, 78, 9, 1234567890123]);
+		assert!(!input.has_more());
+	}
+
+	#[test]
+	fn for_each_u64_after_other_reads() {
+		let mut input = reader("3 1 2 3");
+		assert_eq!(input.next_u64(), 3);
+		let mut sum = 0;
+		input.for_each_u64(|x| sum += x);
+		assert_eq!(sum, 6);
+	}
+
+	#[test]
+	fn peek_token_type() {
+		let cases = [
+			("42", TokenType::Integer),
+			("-7", TokenType::Integer),
+			("+007", TokenType::Integer),
+			("3.5", TokenType::Float),
+			("-.5", TokenType::Float),
+			("5.", TokenType::Float),
+			("1e9", TokenType::Float),
+			("2.5E-3", TokenType::Float),
+			("-", TokenType::Word),
+			(".", TokenType::Word),
+			("1e", TokenType::Word),
+			("e5", TokenType::Word),
+			("12ab", TokenType::Word),
+			("", TokenType::Eof),
+			("  \n ", TokenType::Eof),
+		];
+		for &(token, expected) in &cases {
+			assert_eq!(reader(token).peek_token_type(), expected, "{:?}", token);
+		}
+	}
+
+	#[test]
+	fn peek_token_type_does_not_consume() {
+		let mut input = reader("  123456789.5 x");
+		input.set_buf_size(4);
+		assert_eq!(input.peek_token_type(), TokenType::Float);
+		assert_eq!(input.next_f64(), 123456789.5);
+		assert_eq!(input.peek_token_type(), TokenType::Word);
+		assert_eq!(input.next_word(), "x");
+		assert_eq!(input.peek_token_type(), TokenType::Eof);
+	}
+
+	#[test]
+	fn signs_and_leading_zeros() {
+		let cases: [(&str, Result<i64, ReadError>); 11] = [
+			("007", Ok(7)),
+			("-00", Ok(0)),
+			("+0", Ok(0)),
+			("-007", Ok(-7)),
+			("+42", Ok(42)),
+			("9223372036854775807", Ok(i64::MAX)),
+			("-9223372036854775808", Ok(i64::MIN)),
+			(
+				"9223372036854775808",
+				Err(ReadError::InvalidNumber("9223372036854775808".to_string())),
+			),
+			("-", Err(ReadError::InvalidNumber("-".to_string()))),
+			("+", Err(ReadError::InvalidNumber("+".to_string()))),
+			("--1", Err(ReadError::InvalidNumber("--1".to_string()))),
+		];
+		for (token, expected) in cases.iter() {
+			assert_eq!(&reader(token).try_next_i64(), expected, "{:?}", token);
+		}
+	}
+
+	#[test]
+	fn next_i64_skips_lone_signs() {
+		let mut input = reader("007 -00 +0 - + -x -5 +-3");
+		let nums: Vec<i64> = (0..5).map(|_| input.next_i64()).collect();
+		assert_eq!(nums, [7, 0, 0, -5, -3]);
+	}
+
+	#[test]
+	fn strict_accepts_whitespace_and_signs() {
+		let mut input = reader(" 5\n\t-3 +2 1.5");
+		input.set_strict(true);
+		assert_eq!(input.next_u64(), 5);
+		assert_eq!(input.next_i64(), -3);
+		assert_eq!(input.next_i64(), 2);
+		assert_eq!(input.next_f64(), 1.5);
+	}
+
+	#[test]
+	#[should_panic(expected = "Expected a number, found 'a' while reading u64")]
+	fn strict_rejects_words() {
+		let mut input = reader("abc 5");
+		input.set_strict(true);
+		input.next_u64();
+	}
+
+	#[test]
+	#[should_panic(expected = "Expected a number, found ' ' while reading i64")]
+	fn strict_rejects_lone_sign() {
+		let mut input = reader("- 5");
+		input.set_strict(true);
+		input.next_i64();
+	}
+
+	#[test]
+	fn non_strict_skips_words() {
+		assert_eq!(reader("abc 5").next_u64(), 5);
+	}
+
+	#[test]
+	fn next_base64() {
+		let mut input = reader("TWFu TQ== TWE= TWFueQ== +/+/");
+		assert_eq!(input.next_base64(), b"Man");
+		assert_eq!(input.next_base64(), b"M");
+		assert_eq!(input.next_base64(), b"Ma");
+		assert_eq!(input.next_base64(), b"Many");
+		assert_eq!(input.next_base64(), [0xfb, 0xff, 0xbf]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid base64 padding while reading base64")]
+	fn next_base64_bad_length() {
+		reader("TWF").next_base64();
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid base64 padding while reading base64")]
+	fn next_base64_inner_padding() {
+		reader("TQ=u").next_base64();
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid base64 character '.' while reading base64")]
+	fn next_base64_bad_char() {
+		reader("TW.u").next_base64();
+	}
+
+	#[test]
+	fn next_adjacency_matrix_both_formats() {
+		let expected = vec![
+			vec![false, true, false],
+			vec![true, false, true],
+			vec![false, true, false],
+		];
+		let mut input = reader("\n0 1 0\n1 0 1\n0 1 0\n010\n101\n010\n");
+		assert_eq!(input.next_adjacency_matrix(3), expected);
+		assert_eq!(input.next_adjacency_matrix(3), expected);
+	}
+
+	#[test]
+	#[should_panic(expected = "Row has 2 entries, expected 3 while reading adjacency matrix")]
+	fn next_adjacency_matrix_short_row() {
+		reader("010\n10\n010\n").next_adjacency_matrix(3);
+	}
+
+	#[test]
+	fn expect_one_of() {
+		let options = ["GET", "PUT", "DELETE"];
+		let mut input = reader("PUT GET get");
+		assert_eq!(input.expect_one_of(&options), 1);
+		assert_eq!(input.try_expect_one_of(&options), Ok(0));
+		assert_eq!(
+			input.try_expect_one_of(&options),
+			Err(ReadError::NoMatch("get".to_string()))
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "Expected one of [\"GET\", \"PUT\"], got \"POST\"")]
+	fn expect_one_of_no_match() {
+		reader("POST").expect_one_of(&["GET", "PUT"]);
+	}
+
+	#[test]
+	fn line_words() {
+		let mut input = reader("a bb  ccc\n\nd\n");
+		assert_eq!(input.line_words().collect::<Vec<_>>(), ["a", "bb", "ccc"]);
+		assert_eq!(input.line_words().count(), 0);
+		assert_eq!(input.line_words().collect::<Vec<_>>(), ["d"]);
+	}
+
+	// returns at most `chunk` bytes per read, to split tokens between reads
+	struct Chunked<'a> {
+		input: &'a [u8],
+		chunk: usize,
+	}
+
+	impl<'a> Read for Chunked<'a> {
+		fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+			let n = self.chunk.min(out.len()).min(self.input.len());
+			out[..n].copy_from_slice(&self.input[..n]);
+			self.input = &self.input[n..];
+			Ok(n)
+		}
+	}
+
+	#[test]
+	fn tokens_across_window_boundaries() {
+		let text = "12345678 -987654 +42 word-with-dashes 3.25e-2 -x7 longerthanthebuffer 0";
+		for buf_size in 1..=9 {
+			for chunk in 1..=3 {
+				let mut input = InputReader::from_reader(Chunked {
+					input: text.as_bytes(),
+					chunk,
+				});
+				input.set_buf_size(buf_size);
+				let case = (buf_size, chunk);
+				assert_eq!(input.next_u64(), 12345678, "{:?}", case);
+				assert_eq!(input.next_i64(), -987654, "{:?}", case);
+				assert_eq!(input.next_i64(), 42, "{:?}", case);
+				assert_eq!(input.next_word(), "word-with-dashes", "{:?}", case);
+				assert_eq!(input.next_f64(), 3.25e-2, "{:?}", case);
+				assert_eq!(input.next_i64(), 7, "{:?}", case);
+				assert_eq!(input.next_word(), "longerthanthebuffer", "{:?}", case);
+				assert_eq!(input.next_u64(), 0, "{:?}", case);
+				assert!(!input.has_more(), "{:?}", case);
+			}
+		}
+	}
+
+	#[test]
+	fn drain_to_copies_buffered_and_unread_input() {
+		let text = "3 rest of the input\nmore lines\n";
+		let mut input = InputReader::from_reader(Chunked {
+			input: text.as_bytes(),
+			chunk: 4,
+		});
+		assert_eq!(input.next_u64(), 3);
+		let mut out = Vec::new();
+		assert_eq!(input.drain_to(&mut out).unwrap(), text.len() as u64 - 1);
+		assert_eq!(out, &text.as_bytes()[1..]);
+		assert!(!input.has_more());
+	}
+
+	#[test]
+	fn next_time_hms() {
+		let mut input = reader("01:02:03 2:03 00:00 100:00:00 0:59:59");
+		assert_eq!(input.next_time_hms(), 3723);
+		assert_eq!(input.next_time_hms(), 123);
+		assert_eq!(input.next_time_hms(), 0);
+		assert_eq!(input.next_time_hms(), 360000);
+		assert_eq!(input.next_time_hms(), 3599);
+	}
+
+	#[test]
+	fn next_time_hms_invalid() {
+		let cases = [
+			"1:60",
+			"1:2:60",
+			"12",
+			"1:2:3:4",
+			"1::3",
+			"1:-2",
+			"a:00",
+			"999999999999999999999:00",
+			"999999999999999999:00:00",
+		];
+		for token in cases.iter() {
+			let result = std::panic::catch_unwind(|| reader(token).next_time_hms());
+			let msg = result.expect_err(token).downcast::<String>().unwrap();
+			assert!(msg.contains("Invalid time"), "{}", msg);
+		}
+	}
+
+	#[test]
+	fn next_complex() {
+		let mut input = reader("3+4i -2i i -i 5 -1.5-2.5i 1e-3+2e+1i +i");
+		assert_eq!(input.next_complex(), (3.0, 4.0));
+		assert_eq!(input.next_complex(), (0.0, -2.0));
+		assert_eq!(input.next_complex(), (0.0, 1.0));
+		assert_eq!(input.next_complex(), (0.0, -1.0));
+		assert_eq!(input.next_complex(), (5.0, 0.0));
+		assert_eq!(input.next_complex(), (-1.5, -2.5));
+		assert_eq!(input.next_complex(), (1e-3, 2e1));
+		assert_eq!(input.next_complex(), (0.0, 1.0));
+	}
+
+	#[test]
+	#[should_panic(expected = "Invalid complex number \"3+4j\" while reading complex number")]
+	fn next_complex_invalid() {
+		reader("3+4j").next_complex();
+	}
+
+	#[test]
+	fn word_longer_than_many_windows() {
+		let word: String = (0..5000).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+		let text = format!("{} {}\n1", word, word);
+		let mut input = InputReader::from_reader(Chunked {
+			input: text.as_bytes(),
+			chunk: 7,
+		});
+		input.set_buf_size(16);
+		assert_eq!(input.next_word(), word);
+		assert_eq!(input.next_word(), word);
+		assert_eq!(input.next_u64(), 1);
+	}
+
+	#[test]
+	fn one_byte_input() {
+		let mut input = reader("x");
+		assert!(input.has_more());
+		assert_eq!(input.next_char(), 'x');
+		assert!(!input.has_more());
+
+		assert_eq!(reader("7").next_u64(), 7);
+		assert_eq!(reader("7").next_i64(), 7);
+		assert_eq!(reader("7").next_f64(), 7.0);
+		assert_eq!(reader("a").next_word(), "a");
+		assert_eq!(reader("z").next_line(), "z");
+	}
+
+	#[test]
+	fn next_char_after_whitespace_at_eof() {
+		let mut input = reader("\n y");
+		assert_eq!(input.next_char(), 'y');
+		assert!(!input.has_more());
+	}
+
+	#[test]
+	#[should_panic(expected = "Reached end of input while reading char")]
+	fn next_char_past_eof() {
+		let mut input = reader("x");
+		input.next_char();
+		input.next_char();
+	}
+
+	#[test]
+	fn read_until_seq_across_buffer() {
+		let text = "first\r\n\r\nsecond\r\n\r\nrest";
+		for buf_size in 1..=9 {
+			let mut input = reader(text);
+			input.set_buf_size(buf_size);
+			let mut out = Vec::new();
+			assert!(input.read_until_seq(b"\r\n\r\n", &mut out));
+			assert_eq!(out, b"first\r\n\r\n");
+			assert!(input.read_until_seq(b"\r\n\r\n", &mut out));
+			assert_eq!(out, b"first\r\n\r\nsecond\r\n\r\n");
+			out.clear();
+			assert!(!input.read_until_seq(b"\r\n\r\n", &mut out));
+			assert_eq!(out, b"rest");
+		}
+	}
+
+	#[test]
+	fn read_until_seq_overlapping() {
+		let mut input = reader("aaab");
+		let mut out = Vec::new();
+		assert!(input.read_until_seq(b"aab", &mut out));
+		assert_eq!(out, b"aaab");
+	}
+
+	#[test]
+	fn count_lines_keeps_cursor() {
+		let text = format!("3\n{}", "a line\n".repeat(1000));
+		let mut input = InputReader::from_reader(Chunked {
+			input: text.as_bytes(),
+			chunk: 100,
+		});
+		input.set_buf_size(16);
+		assert_eq!(input.next_u64(), 3);
+		assert_eq!(input.count_lines(), 1001);
+		assert_eq!(input.next_word(), "a");
+		assert_eq!(input.count_lines(), 1000);
+		assert_eq!(input.next_line(), " line");
+		assert_eq!(input.count_lines(), 999);
+	}
+
+	#[test]
+	fn count_lines_unread_token() {
+		let mut input = reader("1 2\n3");
+		input.next_u64();
+		input.count_lines();
+		input.unread_last();
+		assert_eq!(input.next_u64(), 1);
+		assert_eq!(reader("").count_lines(), 0);
+	}
+
+	#[test]
+	fn try_from_file() {
+		let path = std::env::temp_dir().join(format!("easy_io_{}.txt", std::process::id()));
+		std::fs::write(&path, "5\n").unwrap();
+		let result = InputReader::try_from_file(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(result.unwrap().next_u64(), 5);
+
+		match InputReader::try_from_file(&path) {
+			Err(InputError::NotFound(p)) => assert_eq!(p, path),
+			Err(e) => panic!("unexpected error {}", e),
+			Ok(_) => panic!("opened a removed file"),
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn try_from_file_permission_denied() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let path = std::env::temp_dir().join(format!("easy_io_{}.locked", std::process::id()));
+		std::fs::write(&path, "5\n").unwrap();
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+		let result = InputReader::try_from_file(&path);
+		// root can open the file anyway, so there is nothing to test then
+		let privileged = File::open(&path).is_ok();
+		std::fs::remove_file(&path).unwrap();
+		if privileged {
+			return;
+		}
+		match result {
+			Err(InputError::PermissionDenied(p)) => assert_eq!(p, path),
+			Err(e) => panic!("unexpected error {}", e),
+			Ok(_) => panic!("opened an unreadable file"),
+		}
+	}
+
+	#[test]
+	fn next_grid_checked() {
+		let mut input = reader("2\r\n\r\n#.#\r\n.#.\r\n");
+		let rows = input.next_usize();
+		let grid = input.next_grid_checked(rows).unwrap();
+		assert_eq!(grid, vec![vec!['#', '.', '#'], vec!['.', '#', '.']]);
+		assert_eq!(input.next_grid_checked(0), Ok(Vec::new()));
+	}
+
+	#[test]
+	fn next_grid_checked_ragged() {
+		let mut input = reader("abc\nabc\nab\n");
+		assert_eq!(
+			input.next_grid_checked(3),
+			Err(GridError {
+				row: 2,
+				len: 2,
+				expected: 3
+			})
+		);
+	}
+
+	#[test]
+	fn next_grid_checked_zero_rows_at_eof() {
+		let mut input = reader("");
+		assert_eq!(input.next_grid_checked(0), Ok(Vec::new()));
+	}
+
+	#[test]
+	fn non_ascii_bytes_separate_words() {
+		let mut input = reader("a\u{a0}b\u{3000}c na\u{ef}ve");
+		let words: Vec<String> = (0..5).map(|_| input.next_word().to_string()).collect();
+		assert_eq!(words, ["a", "b", "c", "na", "ve"]);
+	}
+
+	#[test]
+	fn unread_last() {
+		let mut input = reader("word -12 34 2.5 end");
+		assert_eq!(input.next_word(), "word");
+		input.unread_last();
+		assert_eq!(input.next_word(), "word");
+		assert_eq!(input.next_i64(), -12);
+		input.unread_last();
+		assert_eq!(input.next_i64(), -12);
+		assert_eq!(input.next_u64(), 34);
+		input.unread_last();
+		assert_eq!(input.next_word(), "34");
+		assert_eq!(input.next_f64(), 2.5);
+		input.unread_last();
+		assert_eq!(input.next_f64(), 2.5);
+		assert_eq!(input.next_word(), "end");
+	}
+
+	#[test]
+	fn unread_last_across_refill() {
+		let mut input = InputReader::from_reader(Chunked {
+			input: b"ab 123456 x",
+			chunk: 2,
+		});
+		input.set_buf_size(8);
+		assert_eq!(input.next_word(), "ab");
+		assert_eq!(input.next_u64(), 123456);
+		input.unread_last();
+		assert_eq!(input.next_u64(), 123456);
+		assert_eq!(input.next_word(), "x");
+	}
+
+	#[test]
+	#[should_panic(expected = "No token to unread")]
+	fn unread_last_twice() {
+		let mut input = reader("a b");
+		input.next_word();
+		input.unread_last();
+		input.unread_last();
+	}
+
+	#[test]
+	#[should_panic(expected = "InputReader: No token to unread!")]
+	fn unread_last_after_other_read() {
+		let mut input = reader("a b c");
+		input.next_word();
+		input.next_char();
+		input.unread_last();
+	}
+
+	#[test]
+	#[should_panic(expected = "No token to unread")]
+	fn unread_last_longer_than_buffer() {
+		let mut input = reader("abcdefghijklmnop");
+		input.set_buf_size(4);
+		input.next_word();
+		input.unread_last();
+	}
+
+	#[test]
+	#[should_panic(expected = "Reached end of input while reading i64 at line 2, col 3!")]
+	fn fail_reports_position_and_op() {
+		let mut input = reader("12\n  ");
+		input.set_track_position(true);
+		assert_eq!(input.next_i64(), 12);
+		input.next_i64();
+	}
+
+	#[test]
+	#[should_panic(expected = "Reached end of input while reading u64 at line 3, col 6!")]
+	fn fail_position_survives_buffer_compaction() {
+		let mut input = reader("1 2 3\n4 5 6\n7 8 x");
+		input.set_buf_size(4);
+		input.set_track_position(true);
+		for _ in 0..8 {
+			input.next_u64();
+		}
+		input.next_u64();
+	}
+
+	#[test]
+	#[should_panic(
+		expected = "Invalid adjacency matrix entry \"2\" while reading adjacency matrix!"
+	)]
+	fn adjacency_matrix_reports_its_own_op() {
+		let mut input = reader("3\n0 2 0\n");
+		input.next_usize();
+		input.next_adjacency_matrix(3);
+	}
+
+	#[test]
+	#[should_panic(expected = "while reading grid!")]
+	fn grid_reports_its_own_op() {
+		let mut input = reader("2\n");
+		let rows = input.next_usize();
+		let _ = input.next_grid_checked(rows);
+	}
+
+	#[test]
+	#[should_panic(expected = "Read error (broken pipe) while reading u64!")]
+	fn read_errors_panic_with_op() {
+		InputReader::from_reader(FailingReader).next_u64();
+	}
+
+	#[test]
+	fn next_f64_reads_leading_decimal_point() {
+		let mut input = reader(".5 -.25 +.75 x.5");
+		for &expected in &[0.5, -0.25, 0.75] {
+			assert_eq!(input.peek_token_type(), TokenType::Float);
+			assert_eq!(input.next_f64(), expected);
+		}
+		assert_eq!(input.next_f64(), 0.5);
+	}
+
+	#[test]
+	fn next_f64_leading_decimal_point_across_buffer() {
+		let mut input = reader("abc.5 .-.1");
+		input.set_buf_size(4);
+		assert_eq!(input.next_f64(), 0.5);
+		assert_eq!(input.next_f64(), -0.1);
+	}
+
+	#[test]
+	fn strict_next_f64_accepts_leading_decimal_point() {
+		let mut input = reader(".5 -.5");
+		input.set_strict(true);
+		assert_eq!(input.next_f64(), 0.5);
+		assert_eq!(input.next_f64(), -0.5);
+	}
+
+	#[test]
+	fn strict_unsigned_accepts_plus_sign() {
+		let mut input = reader("+5 +2 1 3");
+		input.set_strict(true);
+		assert_eq!(input.next_u64(), 5);
+		assert_eq!(input.next_permutation(3, true), vec![1, 0, 2]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Expected a number, found ' ' while reading u64")]
+	fn strict_unsigned_rejects_lone_plus_sign() {
+		let mut input = reader("+ 5");
+		input.set_strict(true);
+		input.next_u64();
+	}
+
+	#[test]
+	fn strict_for_each_u64() {
+		let mut input = reader(" 1 +2\n3 ");
+		input.set_strict(true);
+		let mut nums = Vec::new();
+		input.for_each_u64(|x| nums.push(x));
+		assert_eq!(nums, [1, 2, 3]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Expected a number, found 'a' while reading u64")]
+	fn strict_for_each_u64_rejects_words() {
+		let mut input = reader("abc 5");
+		input.set_strict(true);
+		input.for_each_u64(|_| {});
+	}
 }
@@ -1,8 +1,17 @@
+#[cfg(feature = "async")]
+extern crate futures_io;
+
+#[cfg(feature = "async")]
+pub mod async_input_reader;
 pub mod input_reader;
 pub mod output_writer;
 
-pub use input_reader::InputReader;
-pub use output_writer::OutputWriter;
+#[cfg(feature = "async")]
+pub use async_input_reader::AsyncInputReader;
+pub use input_reader::{GridError, InputError, InputReader, LineOverflow, ReadError, TokenType};
+pub use output_writer::{
+	AtomicFile, CheckedOutputWriter, CheckedRowBuilder, OutputWriter, RowBuilder,
+};
 
 pub fn stdout_panics() {
 	std::panic::set_hook(Box::new(|panic_info| {
@@ -7,13 +7,34 @@
   2019
 */
 
-use std::fmt::Display;
+use std::fmt::{self, Display};
 use std::fs::File;
 use std::io::{self, Result, Stdout, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+const BUF_SIZE: usize = 1 << 16;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
 pub struct OutputWriter<W: Write> {
 	writer: W,
 	buf: Vec<u8>,
+	trim_trailing_spaces: bool,
+	newline: &'static str,
+	checksum: Option<u64>,
+	bytes_written: usize,
+	indent: usize,
+	// start of the text after the last line end, and whether its first line was partly flushed
+	line_start: usize,
+	line_flushed: bool,
+	checked: bool,
+	case: usize,
+	flush_every_lines: usize,
+	lines: usize,
 }
 
 impl OutputWriter<Stdout> {
@@ -22,6 +43,12 @@ impl OutputWriter<Stdout> {
 	}
 }
 
+impl Default for OutputWriter<Stdout> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl OutputWriter<File> {
 	pub fn from_file(path: impl AsRef<std::path::Path>) -> Self {
 		Self::from_writer(File::create(path).unwrap())
@@ -35,38 +62,353 @@ impl OutputWriter<File> {
 	}
 }
 
+impl OutputWriter<AtomicFile> {
+	/// output goes to a temporary file next to `path`, which is renamed over `path` when the
+	/// writer is dropped. If writing fails or the thread panics, the temporary file is removed
+	/// and `path` is left untouched. Panics if the file cannot be synced or renamed
+	pub fn to_file_atomic(path: impl AsRef<Path>) -> Self {
+		let mut file = AtomicFile::create(path).unwrap();
+		file.panic_on_drop = true;
+		Self::from_writer(file)
+	}
+}
+
+#[cfg(feature = "flate2")]
+impl OutputWriter<flate2::write::GzEncoder<File>> {
+	/// output is gzip-compressed on the fly. The gzip trailer is written when the writer is dropped
+	pub fn to_gz_file(path: impl AsRef<std::path::Path>) -> Self {
+		let file = File::create(path).unwrap();
+		Self::from_writer(flate2::write::GzEncoder::new(
+			file,
+			flate2::Compression::default(),
+		))
+	}
+}
+
+impl OutputWriter<TcpStream> {
+	/// each flush sends the whole buffer at once, not line by line
+	pub fn from_tcp(stream: TcpStream) -> Self {
+		Self::from_writer(stream)
+	}
+
+	/// disable Nagle's algorithm so flushed output is sent right away, for interactive use
+	pub fn set_nodelay(&mut self, nodelay: bool) {
+		self.writer.set_nodelay(nodelay).unwrap();
+	}
+}
+
 impl<W: Write> OutputWriter<W> {
 	pub fn from_writer(writer: W) -> Self {
-		let buf = Vec::with_capacity(1 << 16);
-		Self { writer, buf }
+		let buf = Vec::with_capacity(BUF_SIZE);
+		Self {
+			writer,
+			buf,
+			trim_trailing_spaces: false,
+			newline: "\n",
+			checksum: None,
+			bytes_written: 0,
+			indent: 0,
+			line_start: 0,
+			line_flushed: false,
+			checked: false,
+			case: 0,
+			flush_every_lines: 0,
+			lines: 0,
+		}
+	}
+
+	/// when enabled, a final "# fnv1a <hash>" line is written on drop,
+	/// hashing all bytes flushed after enabling it
+	pub fn set_checksum(&mut self, enabled: bool) {
+		self.checksum = if enabled { Some(FNV_OFFSET) } else { None };
+	}
+
+	/// flush after every n lines ended by println or nl, 0 to disable. Default: 0
+	pub fn set_flush_every_lines(&mut self, n: usize) {
+		self.flush_every_lines = n;
+		self.lines = 0;
+	}
+
+	/// set the line terminator used by println, nl and s2nl, e.g. "\r\n"
+	pub fn set_newline(&mut self, nl: &'static str) {
+		self.newline = nl;
+	}
+
+	/// remove spaces and tabs at the end of each line when it is terminated
+	pub fn set_trim_trailing_spaces(&mut self, trim: bool) {
+		self.trim_trailing_spaces = trim;
 	}
 
 	pub fn print<T: Display>(&mut self, t: T) {
 		write!(self, "{}", t).unwrap();
 	}
 
+	/// formats straight into the buffer, e.g. `w.write_args(format_args!("{}-{}", a, b))`
+	pub fn write_args(&mut self, args: fmt::Arguments) {
+		self.write_fmt(args).unwrap();
+	}
+
 	pub fn prints<T: Display>(&mut self, t: T) {
 		write!(self, "{} ", t).unwrap();
 	}
 
 	pub fn println<T: Display>(&mut self, t: T) {
-		writeln!(self, "{}", t).unwrap();
+		write!(self, "{}", t).unwrap();
+		self.finish_line().unwrap();
+	}
+
+	pub fn nl(&mut self) {
+		self.finish_line().unwrap();
+	}
+
+	/// indent lines ended by println or nl by two more spaces. All lines of the text written
+	/// since the previous line end are indented, except a line that was partly flushed already
+	pub fn push_indent(&mut self) {
+		self.indent += 1;
+	}
+
+	pub fn pop_indent(&mut self) {
+		assert!(self.indent > 0, "OutputWriter: No indentation to pop!");
+		self.indent -= 1;
 	}
 
 	pub fn s2nl(&mut self) {
-		match self.buf.last_mut() {
-			Some(last) => match *last {
-				b' ' => *last = b'\n',
-				b'\n' => {}
-				_ => self.buf.push(b'\n'),
-			},
+		match self.buf.last() {
+			Some(b' ') => {
+				self.buf.pop();
+				self.end_line();
+			}
+			Some(b'\n') => {}
+			Some(_) => self.end_line(),
 			None => panic!("Buffer is empty"),
 		}
 	}
 
+	/// prints the transpose of `m`, one row per line with entries separated by `sep`
+	pub fn print_matrix_transposed<T: Display>(&mut self, m: &[Vec<T>], sep: &str) {
+		let cols = m.first().map_or(0, |row| row.len());
+		assert!(
+			m.iter().all(|row| row.len() == cols),
+			"OutputWriter: Cannot transpose a ragged matrix!"
+		);
+		for j in 0..cols {
+			for (i, row) in m.iter().enumerate() {
+				if i > 0 {
+					self.print(sep);
+				}
+				self.print(&row[j]);
+			}
+			self.end_line();
+		}
+	}
+
+	/// writes the shortest representation that round-trips to `x`, using the `ryu` crate
+	/// so that output does not depend on the std version
+	#[cfg(feature = "ryu")]
+	pub fn write_f64_shortest(&mut self, x: f64) {
+		self.buf.extend(ryu::Buffer::new().format(x).as_bytes());
+	}
+
+	/// flushes and returns the total number of bytes written to the sink so far
+	pub fn checkpoint(&mut self) -> Result<usize> {
+		self.flush()?;
+		Ok(self.bytes_written)
+	}
+
+	/// starts a row of space separated fields, terminated by calling end()
+	pub fn row(&mut self) -> RowBuilder<'_, W> {
+		RowBuilder {
+			writer: self,
+			empty: true,
+		}
+	}
+
+	/// writes `data` as standard base64, with padding
+	pub fn print_base64(&mut self, data: &[u8]) {
+		self.buf.reserve((data.len() + 2) / 3 * 4);
+		for chunk in data.chunks(3) {
+			let b = [
+				chunk[0],
+				*chunk.get(1).unwrap_or(&0),
+				*chunk.get(2).unwrap_or(&0),
+			];
+			let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+			for i in 0..4 {
+				if i <= chunk.len() {
+					self.buf.push(BASE64[n >> (18 - 6 * i) & 63]);
+				} else {
+					self.buf.push(b'=');
+				}
+			}
+		}
+	}
+
+	/// writes `n` with an explicit sign, e.g. "+3", "-3" and "+0"
+	pub fn print_signed<T: Into<i64>>(&mut self, n: T) {
+		write!(self, "{:+}", n.into()).unwrap();
+	}
+
+	/// writes a duration in seconds in a human readable form, rounded to one decimal:
+	/// under a millisecond as "12.5us", under a second as "350ms", under a minute as "3.5s",
+	/// and above that as "2m3.5s" or "1h2m3.5s". NaN and infinities are written as "NaN",
+	/// "inf" and "-inf", and durations too long to count in tenths of a second in plain seconds
+	pub fn print_duration(&mut self, secs: f64) {
+		if !secs.is_finite() {
+			write!(self, "{}", secs).unwrap();
+			return;
+		}
+		// round to tenths of each unit before picking one, so 0.99999s is "1s" and not "1000ms"
+		let tenths = |unit: f64| (secs.abs() / unit * 10.0).round();
+		let (us, ms, s) = (tenths(1e-6), tenths(1e-3), tenths(1.0));
+		if secs < 0.0 && us > 0.0 {
+			self.buf.push(b'-');
+		}
+		if us < 1e4 {
+			write!(self, "{}us", us / 10.0).unwrap();
+		} else if ms < 1e4 {
+			write!(self, "{}ms", ms / 10.0).unwrap();
+		} else if s < 600.0 || s >= u64::MAX as f64 {
+			write!(self, "{}s", s / 10.0).unwrap();
+		} else {
+			let tenths = s as u64;
+			let (h, m, s) = (
+				tenths / 36000,
+				tenths / 600 % 60,
+				(tenths % 600) as f64 / 10.0,
+			);
+			if h > 0 {
+				write!(self, "{}h", h).unwrap();
+			}
+			write!(self, "{}m{}s", m, s).unwrap();
+		}
+	}
+
+	/// writes num/den reduced to lowest terms as "p/q", with the sign on the numerator
+	pub fn print_fraction(&mut self, num: i64, den: i64) {
+		assert!(den != 0, "OutputWriter: Fraction with a zero denominator!");
+		let (mut a, mut b) = (num.unsigned_abs(), den.unsigned_abs());
+		while b != 0 {
+			let t = a % b;
+			a = b;
+			b = t;
+		}
+		let (p, q) = (num.unsigned_abs() / a, den.unsigned_abs() / a);
+		let sign = if p != 0 && (num < 0) != (den < 0) {
+			"-"
+		} else {
+			""
+		};
+		write!(self, "{}{}/{}", sign, p, q).unwrap();
+	}
+
+	/// writes the "Case #k: " prefix, so the answer can be chained after it
+	pub fn case(&mut self, k: usize) -> &mut Self {
+		self.case = k;
+		write!(self, "Case #{}: ", k).unwrap();
+		self
+	}
+
+	/// writes the prefix for the case after the previous one, starting at "Case #1: "
+	pub fn next_case(&mut self) -> &mut Self {
+		let k = self.case + 1;
+		self.case(k)
+	}
+
 	pub fn yesno(&mut self, b: bool) {
 		self.println(if b { "YES" } else { "NO" })
 	}
+
+	/// debug dump, one "i: item" line per item with i counting from 0, or from 1 if `one_based`
+	pub fn println_indexed<T: Display, I: IntoIterator<Item = T>>(
+		&mut self,
+		items: I,
+		one_based: bool,
+	) {
+		for (i, t) in items.into_iter().enumerate() {
+			write!(self, "{}: {}", i + one_based as usize, t).unwrap();
+			self.finish_line().unwrap();
+		}
+	}
+}
+
+pub struct RowBuilder<'a, W: Write + 'a> {
+	writer: &'a mut OutputWriter<W>,
+	empty: bool,
+}
+
+impl<'a, W: Write> RowBuilder<'a, W> {
+	pub fn field<T: Display>(mut self, t: T) -> Self {
+		if !self.empty {
+			self.writer.buf.push(b' ');
+		}
+		self.writer.print(t);
+		self.empty = false;
+		self
+	}
+
+	pub fn end(self) {
+		self.writer.end_line();
+	}
+}
+
+// private methods
+impl<W: Write> OutputWriter<W> {
+	// indents the non-empty lines written since the last line end
+	fn indent_lines(&mut self) {
+		if self.indent == 0 {
+			return;
+		}
+		let text = self.buf.split_off(self.line_start.min(self.buf.len()));
+		for (i, line) in text.split(|&c| c == b'\n').enumerate() {
+			if i > 0 {
+				self.buf.push(b'\n');
+			}
+			if !line.is_empty() && (i > 0 || !self.line_flushed) {
+				self.buf
+					.extend(std::iter::repeat(b' ').take(2 * self.indent));
+			}
+			self.buf.extend_from_slice(line);
+		}
+	}
+
+	// ends a line from println or nl, which are indented and count towards flush_every_lines
+	fn finish_line(&mut self) -> Result<()> {
+		self.indent_lines();
+		self.end_line();
+		if self.flush_every_lines > 0 {
+			self.lines += 1;
+			if self.lines >= self.flush_every_lines {
+				self.flush()?;
+			}
+		}
+		Ok(())
+	}
+
+	// writes out everything, followed by the checksum line if enabled
+	fn finish(&mut self) -> Result<()> {
+		if !self.buf.is_empty() {
+			self.s2nl();
+		}
+		self.flush()?;
+		match self.checksum.take() {
+			Some(hash) => {
+				write!(self.writer, "# fnv1a {:016x}{}", hash, self.newline)?;
+				self.writer.flush()
+			}
+			None => Ok(()),
+		}
+	}
+
+	fn end_line(&mut self) {
+		if self.trim_trailing_spaces {
+			while let Some(b' ') | Some(b'\t') = self.buf.last() {
+				self.buf.pop();
+			}
+		}
+		self.buf.extend(self.newline.as_bytes());
+		self.line_start = self.buf.len();
+		self.line_flushed = false;
+	}
 }
 
 impl<W: Write> Write for OutputWriter<W> {
@@ -75,19 +417,1008 @@ impl<W: Write> Write for OutputWriter<W> {
 		Ok(bytes.len())
 	}
 
+	// on error only the bytes the writer did not accept stay buffered, so a retry
+	// does not write anything twice
 	fn flush(&mut self) -> Result<()> {
-		self.writer.write_all(&self.buf)?;
+		let mut written = 0;
+		let mut result = Ok(());
+		while written < self.buf.len() {
+			match self.writer.write(&self.buf[written..]) {
+				Ok(0) => {
+					result = Err(io::Error::new(
+						io::ErrorKind::WriteZero,
+						"failed to write whole buffer",
+					));
+					break;
+				}
+				Ok(n) => written += n,
+				Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+				Err(e) => {
+					result = Err(e);
+					break;
+				}
+			}
+		}
+		if let Some(hash) = self.checksum.as_mut() {
+			for &b in &self.buf[..written] {
+				*hash = (*hash ^ b as u64).wrapping_mul(FNV_PRIME);
+			}
+		}
+		self.bytes_written += written;
+		self.line_flushed |= self.line_start < written;
+		self.line_start = self.line_start.saturating_sub(written);
+		self.buf.drain(..written);
+		result?;
 		self.writer.flush()?;
-		self.buf.clear();
+		self.lines = 0;
 		Ok(())
 	}
 }
 
 impl<W: Write> Drop for OutputWriter<W> {
 	fn drop(&mut self) {
-		if !self.buf.is_empty() {
-			self.s2nl();
+		let result = self.finish();
+		// a CheckedOutputWriter must never panic, flush it to see errors
+		if !self.checked {
+			result.unwrap();
+		}
+	}
+}
+
+/// File that only appears at its final path once it has been completely written, see
+/// OutputWriter::to_file_atomic
+pub struct AtomicFile {
+	file: Option<File>,
+	tmp: PathBuf,
+	path: PathBuf,
+	failed: bool,
+	// whether a failed commit on drop panics, as other IO errors do in OutputWriter
+	panic_on_drop: bool,
+}
+
+impl AtomicFile {
+	/// creates the temporary file next to `path`
+	pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let name = path.file_name().ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"AtomicFile: path has no file name",
+			)
+		})?;
+		let mut tmp_name = std::ffi::OsString::from(".");
+		tmp_name.push(name);
+		tmp_name.push(format!(".{}.tmp", std::process::id()));
+		let tmp = path.with_file_name(tmp_name);
+		let file = File::create(&tmp)?;
+		Ok(Self {
+			file: Some(file),
+			tmp,
+			path,
+			failed: false,
+			panic_on_drop: false,
+		})
+	}
+
+	/// syncs the temporary file and renames it over the path. If a write failed before or this
+	/// fails, the temporary file is removed and the path is left untouched. Dropping the file
+	/// commits it if this was not called, but ignores errors unless it came from to_file_atomic
+	pub fn commit(&mut self) -> Result<()> {
+		let file = self.file.take().ok_or_else(already_committed)?;
+		let result = if self.failed {
+			Err(io::Error::new(
+				io::ErrorKind::Other,
+				"AtomicFile: an earlier write failed",
+			))
+		} else {
+			file.sync_all()
+		};
+		drop(file);
+		let result = result.and_then(|_| std::fs::rename(&self.tmp, &self.path));
+		if result.is_err() {
+			let _ = std::fs::remove_file(&self.tmp);
+		}
+		result
+	}
+
+	fn check<T>(&mut self, result: Result<T>) -> Result<T> {
+		match result {
+			// interrupted calls are retried by the caller and don't lose any output
+			Err(ref e) if e.kind() != io::ErrorKind::Interrupted => self.failed = true,
+			_ => {}
+		}
+		result
+	}
+}
+
+fn already_committed() -> io::Error {
+	io::Error::new(io::ErrorKind::Other, "AtomicFile: already committed")
+}
+
+impl Write for AtomicFile {
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		let result = self
+			.file
+			.as_mut()
+			.ok_or_else(already_committed)?
+			.write(bytes);
+		self.check(result)
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		let result = match self.file.as_mut() {
+			Some(file) => file.flush(),
+			None => Ok(()),
+		};
+		self.check(result)
+	}
+}
+
+impl Drop for AtomicFile {
+	fn drop(&mut self) {
+		if self.file.is_none() {
+			return;
+		}
+		if std::thread::panicking() {
+			self.file = None;
+			let _ = std::fs::remove_file(&self.tmp);
+			return;
+		}
+		if let Err(e) = self.commit() {
+			if self.panic_on_drop {
+				panic!("AtomicFile: Failed to write {:?} ({})!", self.path, e);
+			}
+		}
+	}
+}
+
+/// Same as OutputWriter, but IO errors are returned instead of panicking.
+///
+/// Errors are deferred: output is only written once 64KiB are buffered, or as set by
+/// set_flush_every_lines, so a method returning Ok(()) only means the output was buffered.
+/// A broken sink shows up in the call that flushes the buffer, in checkpoint, or in flush.
+/// Call flush at the end, dropping the writer ignores errors. After an error, only the bytes the
+/// sink did not accept stay buffered, so flushing again retries without writing anything twice.
+pub struct CheckedOutputWriter<W: Write> {
+	inner: OutputWriter<W>,
+}
+
+impl CheckedOutputWriter<Stdout> {
+	pub fn new() -> Self {
+		Self::from_writer(io::stdout())
+	}
+}
+
+impl Default for CheckedOutputWriter<Stdout> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl CheckedOutputWriter<File> {
+	pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
+		Ok(Self::from_writer(File::create(path)?))
+	}
+
+	/// create parent folder if not exists
+	pub fn from_file_cf(path: impl AsRef<std::path::Path>) -> Result<Self> {
+		let path = path.as_ref();
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+		Ok(Self::from_writer(File::create(path)?))
+	}
+}
+
+impl CheckedOutputWriter<AtomicFile> {
+	/// same as OutputWriter::to_file_atomic, but call commit to see whether the file was written
+	pub fn to_file_atomic(path: impl AsRef<Path>) -> Result<Self> {
+		Ok(Self::from_writer(AtomicFile::create(path)?))
+	}
+
+	/// writes out all output and renames the file over its path, see AtomicFile::commit
+	pub fn commit(&mut self) -> Result<()> {
+		self.inner.finish()?;
+		self.inner.writer.commit()
+	}
+}
+
+impl<W: Write> CheckedOutputWriter<W> {
+	pub fn from_writer(writer: W) -> Self {
+		let mut inner = OutputWriter::from_writer(writer);
+		inner.checked = true;
+		Self { inner }
+	}
+
+	pub fn set_checksum(&mut self, enabled: bool) {
+		self.inner.set_checksum(enabled);
+	}
+
+	pub fn set_flush_every_lines(&mut self, n: usize) {
+		self.inner.set_flush_every_lines(n);
+	}
+
+	pub fn set_newline(&mut self, nl: &'static str) {
+		self.inner.set_newline(nl);
+	}
+
+	pub fn set_trim_trailing_spaces(&mut self, trim: bool) {
+		self.inner.set_trim_trailing_spaces(trim);
+	}
+
+	pub fn push_indent(&mut self) {
+		self.inner.push_indent();
+	}
+
+	pub fn pop_indent(&mut self) {
+		self.inner.pop_indent();
+	}
+
+	pub fn print<T: Display>(&mut self, t: T) -> Result<()> {
+		write!(self.inner, "{}", t)?;
+		self.flush_if_full()
+	}
+
+	pub fn write_args(&mut self, args: fmt::Arguments) -> Result<()> {
+		self.inner.write_fmt(args)?;
+		self.flush_if_full()
+	}
+
+	pub fn prints<T: Display>(&mut self, t: T) -> Result<()> {
+		write!(self.inner, "{} ", t)?;
+		self.flush_if_full()
+	}
+
+	pub fn println<T: Display>(&mut self, t: T) -> Result<()> {
+		write!(self.inner, "{}", t)?;
+		self.nl()
+	}
+
+	pub fn nl(&mut self) -> Result<()> {
+		self.inner.finish_line()?;
+		self.flush_if_full()
+	}
+
+	pub fn s2nl(&mut self) -> Result<()> {
+		self.inner.s2nl();
+		self.flush_if_full()
+	}
+
+	pub fn print_matrix_transposed<T: Display>(&mut self, m: &[Vec<T>], sep: &str) -> Result<()> {
+		self.inner.print_matrix_transposed(m, sep);
+		self.flush_if_full()
+	}
+
+	#[cfg(feature = "ryu")]
+	pub fn write_f64_shortest(&mut self, x: f64) -> Result<()> {
+		self.inner.write_f64_shortest(x);
+		self.flush_if_full()
+	}
+
+	pub fn checkpoint(&mut self) -> Result<usize> {
+		self.inner.checkpoint()
+	}
+
+	pub fn row(&mut self) -> CheckedRowBuilder<'_, W> {
+		CheckedRowBuilder {
+			writer: self,
+			empty: true,
+		}
+	}
+
+	pub fn print_base64(&mut self, data: &[u8]) -> Result<()> {
+		self.inner.print_base64(data);
+		self.flush_if_full()
+	}
+
+	pub fn print_signed<T: Into<i64>>(&mut self, n: T) -> Result<()> {
+		self.inner.print_signed(n);
+		self.flush_if_full()
+	}
+
+	pub fn print_duration(&mut self, secs: f64) -> Result<()> {
+		self.inner.print_duration(secs);
+		self.flush_if_full()
+	}
+
+	pub fn print_fraction(&mut self, num: i64, den: i64) -> Result<()> {
+		self.inner.print_fraction(num, den);
+		self.flush_if_full()
+	}
+
+	pub fn case(&mut self, k: usize) -> Result<&mut Self> {
+		self.inner.case(k);
+		self.flush_if_full()?;
+		Ok(self)
+	}
+
+	pub fn next_case(&mut self) -> Result<&mut Self> {
+		self.inner.next_case();
+		self.flush_if_full()?;
+		Ok(self)
+	}
+
+	pub fn yesno(&mut self, b: bool) -> Result<()> {
+		self.println(if b { "YES" } else { "NO" })
+	}
+
+	pub fn println_indexed<T: Display, I: IntoIterator<Item = T>>(
+		&mut self,
+		items: I,
+		one_based: bool,
+	) -> Result<()> {
+		for (i, t) in items.into_iter().enumerate() {
+			write!(self.inner, "{}: {}", i + one_based as usize, t)?;
+			self.nl()?;
+		}
+		Ok(())
+	}
+
+	fn flush_if_full(&mut self) -> Result<()> {
+		if self.inner.buf.len() >= BUF_SIZE {
+			self.inner.flush()?;
+		}
+		Ok(())
+	}
+}
+
+pub struct CheckedRowBuilder<'a, W: Write + 'a> {
+	writer: &'a mut CheckedOutputWriter<W>,
+	empty: bool,
+}
+
+impl<'a, W: Write> CheckedRowBuilder<'a, W> {
+	pub fn field<T: Display>(mut self, t: T) -> Self {
+		if !self.empty {
+			self.writer.inner.buf.push(b' ');
+		}
+		self.writer.inner.print(t);
+		self.empty = false;
+		self
+	}
+
+	pub fn end(self) -> Result<()> {
+		self.writer.inner.end_line();
+		self.writer.flush_if_full()
+	}
+}
+
+impl<W: Write> Write for CheckedOutputWriter<W> {
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		self.inner.write_all(bytes)?;
+		self.flush_if_full()?;
+		Ok(bytes.len())
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		self.inner.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	fn output<F: FnOnce(&mut OutputWriter<Vec<u8>>)>(f: F) -> String {
+		let mut w = OutputWriter::from_writer(Vec::new());
+		f(&mut w);
+		w.flush().unwrap();
+		String::from_utf8(w.writer.clone()).unwrap()
+	}
+
+	// keeps what was written readable after the writer is dropped
+	#[derive(Clone, Default)]
+	struct SharedSink(Rc<RefCell<Vec<u8>>>);
+
+	impl SharedSink {
+		fn contents(&self) -> String {
+			String::from_utf8(self.0.borrow().clone()).unwrap()
+		}
+	}
+
+	impl Write for SharedSink {
+		fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+			self.0.borrow_mut().extend(bytes);
+			Ok(bytes.len())
+		}
+
+		fn flush(&mut self) -> Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn nl_keeps_trailing_spaces_by_default() {
+		let out = output(|w| {
+			w.prints("a");
+			w.prints("b");
+			w.nl();
+		});
+		assert_eq!(out, "a b \n");
+	}
+
+	#[test]
+	fn trim_trailing_spaces() {
+		let out = output(|w| {
+			w.set_trim_trailing_spaces(true);
+			w.prints("a");
+			w.prints("b");
+			w.nl();
+			w.print("c \t");
+			w.println("");
+		});
+		assert_eq!(out, "a b\nc\n");
+	}
+
+	#[test]
+	fn crlf_newline() {
+		let out = output(|w| {
+			w.set_newline("\r\n");
+			w.println(1);
+			w.prints(2);
+			w.s2nl();
+			w.s2nl();
+			w.print(3);
+			w.nl();
+		});
+		assert_eq!(out, "1\r\n2\r\n3\r\n");
+	}
+
+	#[test]
+	fn print_matrix_transposed() {
+		let m = vec![vec![1, 2, 3], vec![4, 5, 6]];
+		let out = output(|w| w.print_matrix_transposed(&m, " "));
+		assert_eq!(out, "1 4\n2 5\n3 6\n");
+	}
+
+	#[test]
+	#[should_panic(expected = "Cannot transpose a ragged matrix")]
+	fn print_matrix_transposed_ragged() {
+		output(|w| w.print_matrix_transposed(&[vec![1, 2], vec![3]], " "));
+	}
+
+	#[test]
+	fn checksum_line() {
+		let sinks = [SharedSink::default(), SharedSink::default()];
+		for sink in &sinks {
+			let mut w = OutputWriter::from_writer(sink.clone());
+			w.set_checksum(true);
+			w.print("hel");
+			w.flush().unwrap();
+			w.println("lo");
+		}
+		assert_eq!(sinks[0].contents(), "hello\n# fnv1a a9bc80cca21f28b3\n");
+		assert_eq!(sinks[0].contents(), sinks[1].contents());
+	}
+
+	#[test]
+	fn checksum_skips_output_before_enabling() {
+		let sink = SharedSink::default();
+		{
+			let mut w = OutputWriter::from_writer(sink.clone());
+			w.println("ignored");
+			w.flush().unwrap();
+			w.set_checksum(true);
+			w.println("hello");
+		}
+		assert_eq!(
+			sink.contents(),
+			"ignored\nhello\n# fnv1a a9bc80cca21f28b3\n"
+		);
+	}
+
+	#[test]
+	fn from_tcp_loopback() {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		let server = std::thread::spawn(move || {
+			let mut received = String::new();
+			let (mut stream, _) = listener.accept().unwrap();
+			io::Read::read_to_string(&mut stream, &mut received).unwrap();
+			received
+		});
+
+		{
+			let mut w = OutputWriter::from_tcp(TcpStream::connect(addr).unwrap());
+			w.set_nodelay(true);
+			w.println("hello");
+			w.flush().unwrap();
+			w.println(42);
 		}
-		self.flush().unwrap();
+		assert_eq!(server.join().unwrap(), "hello\n42\n");
+	}
+
+	#[cfg(feature = "ryu")]
+	#[test]
+	fn write_f64_shortest() {
+		let nums = [
+			0.1,
+			0.1 + 0.2,
+			1.0,
+			-0.0,
+			1e21,
+			5e-324,
+			f64::MAX,
+			123456.789,
+		];
+		let out = output(|w| {
+			for &x in &nums {
+				w.write_f64_shortest(x);
+				w.print(' ');
+			}
+		});
+		assert_eq!(
+			out,
+			"0.1 0.30000000000000004 1.0 -0.0 1e21 5e-324 1.7976931348623157e308 123456.789 "
+		);
+		for (s, &x) in out.split_whitespace().zip(&nums) {
+			assert_eq!(s.parse::<f64>().unwrap().to_bits(), x.to_bits());
+		}
+	}
+
+	#[test]
+	fn checkpoint_counts_flushed_bytes() {
+		let mut w = OutputWriter::from_writer(Vec::new());
+		assert_eq!(w.checkpoint().unwrap(), 0);
+		w.println("hello");
+		assert_eq!(w.checkpoint().unwrap(), 6);
+		w.print(42);
+		assert_eq!(w.writer, b"hello\n");
+		assert_eq!(w.checkpoint().unwrap(), 8);
+		assert_eq!(w.writer, b"hello\n42");
+	}
+
+	#[test]
+	fn row_builder() {
+		let out = output(|w| {
+			w.row().field(1).field(2).end();
+			w.row().field("x").field(2.5).field('c').end();
+			w.row().end();
+		});
+		assert_eq!(out, "1 2\nx 2.5 c\n\n");
+	}
+
+	#[test]
+	fn print_base64() {
+		let out = output(|w| {
+			for data in ["", "M", "Ma", "Man", "Many", "\u{ff}\u{fe}"].iter() {
+				w.print_base64(data.as_bytes());
+				w.print(' ');
+			}
+			w.print_base64(&[0xfb, 0xff, 0xbf]);
+		});
+		assert_eq!(out, " TQ== TWE= TWFu TWFueQ== w7/Dvg== +/+/");
+	}
+
+	#[test]
+	fn write_args() {
+		let out = output(|w| {
+			let (a, b) = (3, "x");
+			w.write_args(format_args!("{}-{}", a, b));
+			w.write_args(format_args!("{:>4}", a));
+		});
+		assert_eq!(out, "3-x   3");
+	}
+
+	#[test]
+	fn nested_indent() {
+		let out = output(|w| {
+			w.println("root");
+			w.push_indent();
+			w.println("child");
+			w.push_indent();
+			w.print("grand");
+			w.println("child");
+			w.pop_indent();
+			w.prints("x");
+			w.nl();
+			w.pop_indent();
+			w.println("end");
+		});
+		assert_eq!(out, "root\n  child\n    grandchild\n  x \nend\n");
+	}
+
+	#[test]
+	fn indent_every_line_of_the_text() {
+		let out = output(|w| {
+			w.push_indent();
+			w.println("a\nb");
+			w.print("c\n\nd");
+			w.nl();
+		});
+		assert_eq!(out, "  a\n  b\n  c\n\n  d\n");
+	}
+
+	#[test]
+	fn indent_after_mid_line_flush() {
+		let out = output(|w| {
+			w.push_indent();
+			w.println("a");
+			w.print("b");
+			w.flush().unwrap();
+			w.println("c\nd");
+			w.println("e");
+		});
+		assert_eq!(out, "  a\nbc\n  d\n  e\n");
+	}
+
+	#[test]
+	fn indent_skips_lines_not_ended_by_println() {
+		let out = output(|w| {
+			w.push_indent();
+			w.row().field(1).field(2).end();
+			w.println("x");
+		});
+		assert_eq!(out, "1 2\n  x\n");
+	}
+
+	#[test]
+	#[should_panic(expected = "No indentation to pop")]
+	fn pop_indent_without_push() {
+		output(|w| w.pop_indent());
+	}
+
+	struct BrokenSink;
+
+	impl Write for BrokenSink {
+		fn write(&mut self, _: &[u8]) -> Result<usize> {
+			Err(io::ErrorKind::BrokenPipe.into())
+		}
+
+		fn flush(&mut self) -> Result<()> {
+			Ok(())
+		}
+	}
+
+	type Call = fn(&mut CheckedOutputWriter<BrokenSink>) -> Result<()>;
+
+	#[test]
+	fn checked_methods_propagate_sink_errors() {
+		let calls: Vec<(&str, Call)> = vec![
+			("print", |w| w.print(1)),
+			("write_args", |w| w.write_args(format_args!("{}", 1))),
+			("prints", |w| w.prints(1)),
+			("println", |w| w.println(1)),
+			("nl", |w| w.nl()),
+			("s2nl", |w| w.s2nl()),
+			("print_matrix_transposed", |w| {
+				w.print_matrix_transposed(&[vec![1]], " ")
+			}),
+			("checkpoint", |w| w.checkpoint().map(|_| ())),
+			("print_base64", |w| w.print_base64(b"x")),
+			("print_signed", |w| w.print_signed(1)),
+			("print_duration", |w| w.print_duration(1.0)),
+			("print_fraction", |w| w.print_fraction(1, 2)),
+			("case", |w| w.case(1).map(|_| ())),
+			("next_case", |w| w.next_case().map(|_| ())),
+			("yesno", |w| w.yesno(true)),
+			("println_indexed", |w| w.println_indexed(vec![1], false)),
+			("row", |w| w.row().field(1).end()),
+			("write", |w| w.write(b"x").map(|_| ())),
+			("flush", |w| w.flush()),
+			#[cfg(feature = "ryu")]
+			("write_f64_shortest", |w| w.write_f64_shortest(1.0)),
+		];
+		for (name, call) in calls {
+			let mut w = CheckedOutputWriter::from_writer(BrokenSink);
+			// one more byte fills the buffer and makes the call flush
+			w.inner.buf.resize(BUF_SIZE - 1, b'x');
+			let err = call(&mut w).expect_err(name);
+			assert_eq!(err.kind(), io::ErrorKind::BrokenPipe, "{}", name);
+		}
+	}
+
+	#[test]
+	fn checked_errors_are_deferred_until_flush() {
+		let mut w = CheckedOutputWriter::from_writer(BrokenSink);
+		assert!(w.println("buffered").is_ok());
+		assert!(w.flush().is_err());
+	}
+
+	#[test]
+	fn checked_flush_every_lines_reports_errors_per_line() {
+		let mut w = CheckedOutputWriter::from_writer(BrokenSink);
+		w.set_flush_every_lines(1);
+		assert!(w.print("x").is_ok());
+		assert!(w.println("y").is_err());
+	}
+
+	// accepts `limit` bytes, then fails once and works again
+	struct FlakySink {
+		out: SharedSink,
+		limit: Option<usize>,
+	}
+
+	impl Write for FlakySink {
+		fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+			let len = self.out.0.borrow().len();
+			let n = match self.limit {
+				Some(limit) if len >= limit => {
+					self.limit = None;
+					return Err(io::ErrorKind::BrokenPipe.into());
+				}
+				Some(limit) => bytes.len().min(limit - len),
+				None => bytes.len(),
+			};
+			self.out.write(&bytes[..n])
+		}
+
+		fn flush(&mut self) -> Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn flush_retry_does_not_resend() {
+		let sink = SharedSink::default();
+		{
+			let mut w = CheckedOutputWriter::from_writer(FlakySink {
+				out: sink.clone(),
+				limit: Some(3),
+			});
+			w.set_checksum(true);
+			w.println("hello").unwrap();
+			assert!(w.flush().is_err());
+			assert_eq!(sink.contents(), "hel");
+			w.flush().unwrap();
+			assert_eq!(w.checkpoint().unwrap(), 6);
+		}
+		assert_eq!(sink.contents(), "hello\n# fnv1a a9bc80cca21f28b3\n");
+	}
+
+	#[test]
+	fn print_fraction() {
+		let out = output(|w| {
+			let cases = [
+				(2, -4),
+				(-6, -9),
+				(0, -5),
+				(7, 1),
+				(i64::MIN, -1),
+				(i64::MIN, i64::MIN),
+			];
+			for &(num, den) in cases.iter() {
+				w.print_fraction(num, den);
+				w.print(' ');
+			}
+		});
+		assert_eq!(out, "-1/2 2/3 0/1 7/1 9223372036854775808/1 1/1 ");
+	}
+
+	#[test]
+	#[should_panic(expected = "Fraction with a zero denominator")]
+	fn print_fraction_zero_denominator() {
+		output(|w| w.print_fraction(1, 0));
+	}
+
+	#[test]
+	fn next_case() {
+		let out = output(|w| {
+			for answer in ["a", "b", "c"].iter() {
+				w.next_case().println(answer);
+			}
+			w.case(7).yesno(true);
+			w.next_case().yesno(false);
+		});
+		assert_eq!(
+			out,
+			"Case #1: a\nCase #2: b\nCase #3: c\nCase #7: YES\nCase #8: NO\n"
+		);
+	}
+
+	#[test]
+	fn print_signed() {
+		let out = output(|w| {
+			w.print_signed(3);
+			w.print_signed(-3);
+			w.print_signed(0u8);
+			w.print_signed(i64::MIN);
+		});
+		assert_eq!(out, "+3-3+0-9223372036854775808");
+	}
+
+	#[test]
+	fn flush_every_lines() {
+		let mut w = OutputWriter::from_writer(Vec::new());
+		w.set_flush_every_lines(3);
+		w.println(1);
+		w.print("2\n");
+		w.println(3);
+		assert!(w.writer.is_empty());
+		w.nl();
+		assert_eq!(w.writer, b"1\n2\n3\n\n");
+		w.println(4);
+		w.println(5);
+		assert_eq!(w.writer, b"1\n2\n3\n\n");
+		w.flush().unwrap();
+		w.println(6);
+		w.println(7);
+		assert_eq!(w.writer, b"1\n2\n3\n\n4\n5\n");
+		w.println(8);
+		assert_eq!(w.writer, b"1\n2\n3\n\n4\n5\n6\n7\n8\n");
+	}
+
+	#[cfg(feature = "flate2")]
+	#[test]
+	fn to_gz_file_round_trip() {
+		let path = std::env::temp_dir().join(format!("easy_io_{}.gz", std::process::id()));
+		{
+			let mut w = OutputWriter::to_gz_file(&path);
+			for i in 0..1000 {
+				w.prints(i);
+			}
+			w.println("end");
+		}
+
+		let mut text = String::new();
+		let file = File::open(&path).unwrap();
+		io::Read::read_to_string(&mut flate2::read::GzDecoder::new(file), &mut text).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		let expected: String = (0..1000).map(|i| format!("{} ", i)).collect();
+		assert_eq!(text, expected + "end\n");
+	}
+
+	#[test]
+	fn print_duration() {
+		let cases = [
+			(0.0, "0us"),
+			(1.25e-5, "12.5us"),
+			(0.00099994, "999.9us"),
+			(0.00099999, "1ms"),
+			(0.35, "350ms"),
+			(0.99999, "1s"),
+			(3.5, "3.5s"),
+			(59.99, "1m0s"),
+			(123.5, "2m3.5s"),
+			(3723.5, "1h2m3.5s"),
+			(1e18, "277777777777777h46m40s"),
+			(1e30, "1000000000000000000000000000000s"),
+			(-1e30, "-1000000000000000000000000000000s"),
+			(-3.5, "-3.5s"),
+			(-1e-9, "0us"),
+			(f64::NAN, "NaN"),
+			(f64::INFINITY, "inf"),
+			(f64::NEG_INFINITY, "-inf"),
+		];
+		for &(secs, expected) in cases.iter() {
+			assert_eq!(output(|w| w.print_duration(secs)), expected, "{}", secs);
+		}
+	}
+
+	#[test]
+	fn to_file_atomic() {
+		let dir = std::env::temp_dir().join(format!("easy_io_atomic_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("out.txt");
+		std::fs::write(&path, "old\n").unwrap();
+
+		let result = std::panic::catch_unwind(|| {
+			let mut w = OutputWriter::to_file_atomic(&path);
+			w.println("partial");
+			panic!("solution crashed");
+		});
+		assert!(result.is_err());
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+
+		{
+			let mut w = OutputWriter::to_file_atomic(&path);
+			w.println("new");
+			assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+		}
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+		// no temporary files are left behind
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn atomic_file_needs_a_file_name() {
+		let err = AtomicFile::create("/").err().unwrap();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn println_indexed() {
+		let items = ["a", "b"];
+		assert_eq!(
+			output(|w| w.println_indexed(items.iter(), false)),
+			"0: a\n1: b\n"
+		);
+		assert_eq!(
+			output(|w| w.println_indexed(items.iter(), true)),
+			"1: a\n2: b\n"
+		);
+		assert_eq!(output(|w| w.println_indexed(Vec::<u8>::new(), true)), "");
+	}
+
+	#[test]
+	fn checked_row() {
+		let sink = SharedSink::default();
+		let mut w = CheckedOutputWriter::from_writer(sink.clone());
+		w.row().field(1).field("x").end().unwrap();
+		w.row().end().unwrap();
+		w.flush().unwrap();
+		assert_eq!(sink.contents(), "1 x\n\n");
+	}
+
+	#[test]
+	fn checked_file_constructors() {
+		let dir = std::env::temp_dir().join(format!("easy_io_checked_{}", std::process::id()));
+		let path = dir.join("sub").join("out.txt");
+		{
+			let mut w = CheckedOutputWriter::from_file_cf(&path).unwrap();
+			w.println("cf").unwrap();
+			w.flush().unwrap();
+		}
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "cf\n");
+		{
+			let mut w = CheckedOutputWriter::to_file_atomic(&path).unwrap();
+			w.println("atomic").unwrap();
+			w.flush().unwrap();
+			assert_eq!(std::fs::read_to_string(&path).unwrap(), "cf\n");
+		}
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "atomic\n");
+		assert!(CheckedOutputWriter::to_file_atomic(dir.join("missing").join("x")).is_err());
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn to_file_atomic_panics_if_rename_fails() {
+		let dir = std::env::temp_dir().join(format!("easy_io_rename_{}", std::process::id()));
+		// renaming a file over a directory fails
+		let path = dir.join("out");
+		std::fs::create_dir_all(&path).unwrap();
+
+		let result = std::panic::catch_unwind(|| {
+			let mut w = OutputWriter::to_file_atomic(&path);
+			w.println("lost");
+		});
+		assert!(result.is_err());
+
+		let mut w = CheckedOutputWriter::to_file_atomic(&path).unwrap();
+		w.println("lost").unwrap();
+		assert!(w.commit().is_err());
+		drop(w);
+		// only the directory is left, without temporary files
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn atomic_file_commit() {
+		let dir = std::env::temp_dir().join(format!("easy_io_commit_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("out.txt");
+		let mut file = AtomicFile::create(&path).unwrap();
+		// an interrupted call is retried and must not discard the file
+		let interrupted: Result<()> = Err(io::ErrorKind::Interrupted.into());
+		assert!(file.check(interrupted).is_err());
+		file.write_all(b"done\n").unwrap();
+		file.commit().unwrap();
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "done\n");
+		assert!(file.commit().is_err());
+		assert!(file.write(b"x").is_err());
+		drop(file);
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "done\n");
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn checked_commit_writes_checksum_first() {
+		let dir = std::env::temp_dir().join(format!("easy_io_sum_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("out.txt");
+		let mut w = CheckedOutputWriter::to_file_atomic(&path).unwrap();
+		w.set_checksum(true);
+		w.println("hello").unwrap();
+		w.commit().unwrap();
+		drop(w);
+		assert_eq!(
+			std::fs::read_to_string(&path).unwrap(),
+			"hello\n# fnv1a a9bc80cca21f28b3\n"
+		);
+		std::fs::remove_dir_all(&dir).unwrap();
 	}
 }